@@ -0,0 +1,83 @@
+#![feature(test)]
+extern crate om_wikiparser;
+extern crate test;
+
+use std::collections::HashSet;
+
+use om_wikiparser::{
+    html::simplify,
+    wm::{Page, PageHeader, WikidataQid},
+};
+
+/// Number of pages in the sample dump.
+const PAGES: u32 = 20;
+
+/// A dump of [PAGES] articles of a typical size, with QIDs `Q1` to `Q20`.
+fn sample_dump() -> Vec<Vec<u8>> {
+    let paragraph = "<p>Berlin ist die \\\"Hauptstadt\\\" und ein Land der <a href=\\\"./Deutschland\\\">Bundesrepublik Deutschland</a>.</p>";
+    let section = format!(
+        "<section><h2>Geschichte</h2>{}</section><section><h2>Weblinks</h2><ul><li><a href=\\\"https://berlin.de\\\">berlin.de</a></li></ul></section>",
+        paragraph.repeat(20)
+    );
+    let html = format!(
+        "<html><head></head><body>{}</body></html>",
+        section.repeat(10)
+    );
+
+    (1..=PAGES)
+        .map(|i| {
+            format!(
+                r#"{{"name":"Berlin {i}","date_modified":"2023-06-01T00:00:00Z","in_language":{{"identifier":"de"}},"url":"https://de.wikipedia.org/wiki/Berlin_{i}","main_entity":{{"identifier":"Q{i}"}},"article_body":{{"html":"{html}"}},"redirects":[]}}
+"#
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+/// Run each line of the dump through the extraction loop, without writing anything.
+fn process(dump: &[Vec<u8>], ids: &HashSet<WikidataQid>) -> usize {
+    #[cfg(feature = "simd-json")]
+    let mut buffer = Vec::new();
+    let mut written = 0;
+    for line in dump {
+        let header = PageHeader::from_json_header(line).unwrap();
+        let is_match = header
+            .wikidata()
+            .map(|qid| ids.contains(&qid))
+            .unwrap_or_default();
+        if !is_match {
+            continue;
+        }
+
+        #[cfg(feature = "simd-json")]
+        let page = {
+            buffer.clear();
+            buffer.extend_from_slice(line);
+            Page::from_json(&mut buffer).unwrap()
+        };
+        #[cfg(not(feature = "simd-json"))]
+        let page: Page = header.into_page().unwrap();
+        written += simplify(&page.article_body.html, &page.in_language.identifier).len();
+    }
+    written
+}
+
+#[bench]
+fn process_matching(b: &mut test::Bencher) {
+    let dump = sample_dump();
+    let ids = (1..=PAGES)
+        .map(|i| format!("Q{i}").parse().unwrap())
+        .collect();
+    b.bytes = dump.iter().map(|l| l.len() as u64).sum();
+    b.iter(|| process(&dump, &ids));
+}
+
+/// Most pages in a dump aren't requested.
+#[bench]
+fn process_not_matching(b: &mut test::Bencher) {
+    let dump = sample_dump();
+    let ids = HashSet::from(["Q1000".parse().unwrap()]);
+    b.bytes = dump.iter().map(|l| l.len() as u64).sum();
+    b.iter(|| process(&dump, &ids));
+}
@@ -0,0 +1,97 @@
+#![feature(test)]
+extern crate om_wikiparser;
+extern crate test;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use om_wikiparser::wm::{Page, PageHeader};
+
+/// Counts the bytes allocated by the benchmarks.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// A dump line with an article body of a typical size.
+fn sample_line() -> Vec<u8> {
+    let paragraph = "<p>Berlin ist die \\\"Hauptstadt\\\" und ein Land der <a href=\\\"./Deutschland\\\">Bundesrepublik Deutschland</a>.</p>\\n";
+    let html = paragraph.repeat(500);
+    format!(
+        r#"{{"name":"Berlin","date_modified":"2023-06-01T00:00:00Z","in_language":{{"identifier":"de"}},"url":"https://de.wikipedia.org/wiki/Berlin","main_entity":{{"identifier":"Q64"}},"article_body":{{"html":"{html}"}},"redirects":[{{"url":"https://de.wikipedia.org/wiki/Berlin_(Deutschland)","name":"Berlin (Deutschland)"}}]}}
+"#
+    )
+    .into_bytes()
+}
+
+/// Benchmark `f`, and print the average number of bytes it allocates.
+fn bench_allocations<T>(b: &mut test::Bencher, name: &str, mut f: impl FnMut() -> T) {
+    let mut runs = 0;
+    let start = ALLOCATED.load(Ordering::Relaxed);
+    b.iter(|| {
+        runs += 1;
+        f()
+    });
+    let allocated = ALLOCATED.load(Ordering::Relaxed) - start;
+    eprintln!(
+        "{name}: {} bytes allocated per run",
+        allocated / runs.max(1)
+    );
+}
+
+/// Before: the html is unescaped and copied out of the line just to match the page.
+#[bench]
+fn match_page(b: &mut test::Bencher) {
+    let line = sample_line();
+    bench_allocations(b, "match_page", || {
+        let page: Page = serde_json::from_slice(&line).unwrap();
+        page.wikidata()
+    });
+}
+
+/// After: the html is borrowed from the line.
+#[bench]
+fn match_header(b: &mut test::Bencher) {
+    let line = sample_line();
+    bench_allocations(b, "match_header", || {
+        PageHeader::from_json_header(&line).unwrap().wikidata()
+    });
+}
+
+/// Before: a matching line is parsed again as a full page after its header.
+#[bench]
+fn extract_parse_twice(b: &mut test::Bencher) {
+    let line = sample_line();
+    bench_allocations(b, "extract_parse_twice", || {
+        let header = PageHeader::from_json_header(&line).unwrap();
+        header.wikidata();
+        let page: Page = serde_json::from_slice(&line).unwrap();
+        page
+    });
+}
+
+/// After: only the html of a matching line is unescaped from its header.
+#[bench]
+fn extract_into_page(b: &mut test::Bencher) {
+    let line = sample_line();
+    bench_allocations(b, "extract_into_page", || {
+        let header = PageHeader::from_json_header(&line).unwrap();
+        header.wikidata();
+        header.into_page().unwrap()
+    });
+}
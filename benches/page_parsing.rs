@@ -0,0 +1,48 @@
+#![feature(test)]
+extern crate om_wikiparser;
+extern crate test;
+
+use om_wikiparser::wm::{Page, PageHeader};
+
+/// A dump line with an article body of a typical size.
+fn sample_line() -> Vec<u8> {
+    let paragraph = "<p>Berlin ist die \\\"Hauptstadt\\\" und ein Land der <a href=\\\"./Deutschland\\\">Bundesrepublik Deutschland</a>.</p>\\n";
+    let html = paragraph.repeat(500);
+    format!(
+        r#"{{"name":"Berlin","date_modified":"2023-06-01T00:00:00Z","in_language":{{"identifier":"de"}},"url":"https://de.wikipedia.org/wiki/Berlin","main_entity":{{"identifier":"Q64"}},"article_body":{{"html":"{html}"}},"redirects":[{{"url":"https://de.wikipedia.org/wiki/Berlin_(Deutschland)","name":"Berlin (Deutschland)"}}]}}
+"#
+    )
+    .into_bytes()
+}
+
+#[bench]
+fn parse_page_serde_json(b: &mut test::Bencher) {
+    let line = sample_line();
+    b.bytes = line.len() as u64;
+    b.iter(|| {
+        let page: Page = serde_json::from_slice(&line).unwrap();
+        page
+    });
+}
+
+#[bench]
+fn parse_page_header(b: &mut test::Bencher) {
+    let line = sample_line();
+    b.bytes = line.len() as u64;
+    b.iter(|| PageHeader::from_json_header(&line).unwrap());
+}
+
+#[cfg(feature = "simd-json")]
+#[bench]
+fn parse_page_simd_json(b: &mut test::Bencher) {
+    let line = sample_line();
+    let mut buffer = Vec::with_capacity(line.len());
+    b.bytes = line.len() as u64;
+    b.iter(|| {
+        // Parsing is destructive, so copy the line like reading it from the dump would.
+        buffer.clear();
+        buffer.extend_from_slice(&line);
+        let page: Page = simd_json::from_slice(&mut buffer).unwrap();
+        page
+    });
+}
@@ -0,0 +1,198 @@
+//! Write extracted articles to a tar archive, with the same layout as the output directory.
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use tar::{Builder, EntryType, Header};
+
+use crate::wm::{Page, WikipediaTitleNorm};
+
+/// A tar archive of simplified articles, in `wikidata/{qid}/{lang}.html` files,
+/// with `{lang}.wikipedia.org/wiki/{title}` symlinks to them.
+///
+/// Entries are written in the order they are appended, with fixed owners, permissions, and times,
+/// so the same dump is always written to the same archive.
+pub struct ArticleArchive<W: Write> {
+    builder: Builder<W>,
+}
+
+impl<W: Write> ArticleArchive<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            builder: Builder::new(writer),
+        }
+    }
+
+    /// Append the simplified `html` of `page`, and links to it from each of the `titles`.
+    ///
+    /// Articles without a QID are written to the directory of their first title instead.
+    pub fn append(
+        &mut self,
+        page: &Page,
+        html: &str,
+        titles: &[WikipediaTitleNorm],
+    ) -> anyhow::Result<()> {
+        let main_dir = match page.wikidata() {
+            Some(qid) => qid.get_dir(PathBuf::new()),
+            None => titles
+                .first()
+                .cloned()
+                .or_else(|| page.title().ok())
+                .ok_or_else(|| anyhow!("No available titles for page {:?}", page.name))?
+                .get_dir(PathBuf::new()),
+        };
+
+        let path = main_dir.join(format!("{}.html", page.in_language.identifier));
+        let mut header = Self::header(EntryType::Regular, 0o644);
+        header.set_size(html.len() as u64);
+        self.builder
+            .append_data(&mut header, &path, html.as_bytes())
+            .with_context(|| format!("appending article {:?}", path))?;
+
+        for title in titles {
+            let link = title.get_dir(PathBuf::new());
+            // Same as the output directory, skip titles that would replace or contain the article.
+            if link.starts_with(&main_dir) || main_dir.starts_with(&link) {
+                continue;
+            }
+
+            let target = relative_target(&link, &main_dir);
+            let mut header = Self::header(EntryType::Symlink, 0o777);
+            self.builder
+                .append_link(&mut header, &link, &target)
+                .with_context(|| format!("appending link from {:?} to {:?}", link, target))?;
+        }
+
+        Ok(())
+    }
+
+    fn header(entry_type: EntryType, mode: u32) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_size(0);
+        header
+    }
+
+    /// Write the end of the archive, and return the writer.
+    pub fn finish(self) -> anyhow::Result<W> {
+        self.builder.into_inner().context("finishing archive")
+    }
+}
+
+/// The path to `target` from the directory containing `link`, when both are relative to the archive root.
+///
+/// Titles can contain `/`, so links can be nested at any depth.
+fn relative_target(link: &Path, target: &Path) -> PathBuf {
+    let depth = link.components().count().saturating_sub(1);
+    let mut path = PathBuf::new();
+    for _ in 0..depth {
+        path.push("..");
+    }
+    path.push(target);
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+
+    fn page(name: &str, qid: Option<&str>) -> Page {
+        let mut json = serde_json::json!({
+            "name": name,
+            "date_modified": "2023-06-01T00:00:00Z",
+            "in_language": { "identifier": "en" },
+            "url": format!("https://en.wikipedia.org/wiki/{}", name.replace(' ', "_")),
+            "main_entity": qid.map(|identifier| serde_json::json!({ "identifier": identifier })),
+            "article_body": { "html": "" },
+            "redirects": [],
+        })
+        .to_string()
+        .into_bytes();
+        Page::from_json(&mut json).unwrap()
+    }
+
+    #[test]
+    fn relative_target() {
+        assert_eq!(
+            Path::new("../../wikidata/Q64"),
+            super::relative_target(
+                Path::new("en.wikipedia.org/wiki/Berlin"),
+                Path::new("wikidata/Q64")
+            )
+        );
+        assert_eq!(
+            Path::new("../../../wikidata/Q64"),
+            super::relative_target(
+                Path::new("en.wikipedia.org/wiki/AC/DC"),
+                Path::new("wikidata/Q64")
+            )
+        );
+    }
+
+    #[test]
+    fn append() {
+        let title = |name: &str| WikipediaTitleNorm::from_title(name, "en").unwrap();
+
+        let mut archive = ArticleArchive::new(Vec::new());
+        archive
+            .append(
+                &page("Berlin", Some("Q64")),
+                "<p>Berlin</p>",
+                &[title("Berlin"), title("Berlin, Germany")],
+            )
+            .unwrap();
+        archive
+            .append(&page("Paris", None), "<p>Paris</p>", &[title("Paris")])
+            .unwrap();
+        let bytes = archive.finish().unwrap();
+
+        let mut entries = Vec::new();
+        let mut tar = tar::Archive::new(bytes.as_slice());
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let link = entry.link_name().unwrap().map(|l| l.into_owned());
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            entries.push((entry.header().entry_type(), path, link, contents));
+        }
+
+        assert_eq!(
+            vec![
+                (
+                    EntryType::Regular,
+                    PathBuf::from("wikidata/Q64/en.html"),
+                    None,
+                    "<p>Berlin</p>".to_owned()
+                ),
+                (
+                    EntryType::Symlink,
+                    PathBuf::from("en.wikipedia.org/wiki/Berlin"),
+                    Some(PathBuf::from("../../wikidata/Q64")),
+                    String::new()
+                ),
+                (
+                    EntryType::Symlink,
+                    PathBuf::from("en.wikipedia.org/wiki/Berlin,_Germany"),
+                    Some(PathBuf::from("../../wikidata/Q64")),
+                    String::new()
+                ),
+                (
+                    EntryType::Regular,
+                    PathBuf::from("en.wikipedia.org/wiki/Paris/en.html"),
+                    None,
+                    "<p>Paris</p>".to_owned()
+                ),
+            ],
+            entries
+        );
+    }
+}
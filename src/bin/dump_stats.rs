@@ -0,0 +1,243 @@
+//! Summarize a Wikipedia Enterprise HTML dump read from stdin, without simplifying the articles.
+//!
+//! Lines that can't be deserialized are counted, so this also checks if a dump is corrupt or truncated.
+//!
+//! Usage:
+//!     dump_stats < dump.json
+//!     dump_stats --json < dump.json
+//!     dump_stats --strict < dump.json
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io::{stdin, BufRead},
+};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use serde::Serialize;
+
+use om_wikiparser::wm::Page;
+
+/// Print the number of pages, QIDs, languages, redirects, and html size of the newline-delimited json dump on stdin.
+#[derive(Parser)]
+struct Args {
+    /// Print the summary as a json object instead of a table.
+    #[arg(long)]
+    json: bool,
+
+    /// Exit with an error if any line of the dump can't be deserialized.
+    #[arg(long)]
+    strict: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .try_init()?;
+
+    let args = Args::parse();
+
+    let stats = DumpStats::collect(stdin().lock())?;
+
+    if args.json {
+        let mut json = serde_json::to_value(&stats)?;
+        json["avg_html_bytes"] = stats.avg_html_bytes().into();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        print!("{stats}");
+    }
+
+    if args.strict && stats.invalid_lines > 0 {
+        bail!("{} invalid lines in dump", stats.invalid_lines);
+    }
+
+    Ok(())
+}
+
+/// Number of deserialization errors to keep for the summary.
+const MAX_ERRORS: usize = 5;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct DumpStats {
+    pages: usize,
+    with_qid: usize,
+    without_qid: usize,
+    /// Number of pages in each language.
+    languages: BTreeMap<String, usize>,
+    /// Number of pages with at least one redirect.
+    pages_with_redirects: usize,
+    redirects: usize,
+    html_bytes: usize,
+    /// Number of lines that could not be deserialized.
+    invalid_lines: usize,
+    /// The first [MAX_ERRORS] deserialization errors.
+    errors: Vec<LineError>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct LineError {
+    line: usize,
+    byte: usize,
+    message: String,
+}
+
+impl DumpStats {
+    /// Deserialize each line of `dump` and count its pages, and the lines that aren't valid pages.
+    fn collect(mut dump: impl BufRead) -> anyhow::Result<Self> {
+        let mut stats = Self::default();
+
+        let mut buffer = Vec::new();
+        let mut line = 0;
+        let mut byte = 0;
+        loop {
+            buffer.clear();
+            let len = dump
+                .read_until(b'\n', &mut buffer)
+                .context("reading dump")?;
+            if len == 0 {
+                break;
+            }
+            line += 1;
+            let offset = byte;
+            byte += len;
+
+            match Page::from_json(&mut buffer) {
+                Ok(page) => stats.add(&page),
+                Err(e) => {
+                    stats.invalid_lines += 1;
+                    if stats.errors.len() < MAX_ERRORS {
+                        stats.errors.push(LineError {
+                            line,
+                            byte: offset,
+                            message: format!("{e:#}"),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    fn add(&mut self, page: &Page) {
+        self.pages += 1;
+        if page.main_entity.is_some() {
+            self.with_qid += 1;
+        } else {
+            self.without_qid += 1;
+        }
+        *self
+            .languages
+            .entry(page.in_language.identifier.clone())
+            .or_default() += 1;
+        if !page.redirects.is_empty() {
+            self.pages_with_redirects += 1;
+        }
+        self.redirects += page.redirects.len();
+        self.html_bytes += page.article_body.html.len();
+    }
+
+    fn avg_html_bytes(&self) -> usize {
+        self.html_bytes.checked_div(self.pages).unwrap_or_default()
+    }
+}
+
+impl Display for DumpStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pages                 {}", self.pages)?;
+        writeln!(f, "with qid              {}", self.with_qid)?;
+        writeln!(f, "without qid           {}", self.without_qid)?;
+        writeln!(f, "pages with redirects  {}", self.pages_with_redirects)?;
+        writeln!(f, "redirects             {}", self.redirects)?;
+        writeln!(f, "html bytes            {}", self.html_bytes)?;
+        writeln!(f, "avg html bytes        {}", self.avg_html_bytes())?;
+        writeln!(f, "languages")?;
+        for (lang, count) in &self.languages {
+            writeln!(f, "  {lang:<20}{count}")?;
+        }
+        writeln!(f, "invalid lines         {}", self.invalid_lines)?;
+        for LineError {
+            line,
+            byte,
+            message,
+        } in &self.errors
+        {
+            writeln!(f, "  line {line} (byte {byte}): {message}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn page(lang: &str, name: &str, qid: Option<&str>, redirects: &[&str]) -> String {
+        json!({
+            "name": name,
+            "date_modified": "2023-06-01T00:00:00Z",
+            "in_language": { "identifier": lang },
+            "main_entity": qid.map(|identifier| json!({ "identifier": identifier })),
+            "article_body": { "html": format!("<p>{name}</p>") },
+            "redirects": redirects
+                .iter()
+                .map(|r| json!({ "url": "", "name": r }))
+                .collect::<Vec<_>>(),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn collect() {
+        let dump = [
+            page(
+                "en",
+                "Berlin",
+                Some("Q64"),
+                &["Berlin, Germany", "Berlin (city)"],
+            ),
+            page("en", "Paris", Some("Q90"), &[]),
+            page("de", "Berlin", None, &["Berlin (Stadt)"]),
+        ]
+        .join("\n");
+
+        let stats = DumpStats::collect(dump.as_bytes()).unwrap();
+        assert_eq!(
+            DumpStats {
+                pages: 3,
+                with_qid: 2,
+                without_qid: 1,
+                languages: [("de".to_owned(), 1), ("en".to_owned(), 2)].into(),
+                pages_with_redirects: 2,
+                redirects: 3,
+                html_bytes: "<p>Berlin</p>".len() * 2 + "<p>Paris</p>".len(),
+                ..Default::default()
+            },
+            stats
+        );
+        assert_eq!(12, stats.avg_html_bytes());
+
+        assert_eq!(0, DumpStats::default().avg_html_bytes());
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let good = page("en", "Berlin", Some("Q64"), &[]);
+        let dump = format!("{good}\n{{\"name\": \"Truncat\n{good}");
+
+        let stats = DumpStats::collect(dump.as_bytes()).unwrap();
+        assert_eq!(2, stats.pages);
+        assert_eq!(1, stats.invalid_lines);
+        assert_eq!(1, stats.errors.len());
+        assert_eq!(2, stats.errors[0].line);
+        assert_eq!(good.len() + 1, stats.errors[0].byte);
+
+        let dump = "not json\n".repeat(MAX_ERRORS + 2);
+        let stats = DumpStats::collect(dump.as_bytes()).unwrap();
+        assert_eq!(0, stats.pages);
+        assert_eq!(MAX_ERRORS + 2, stats.invalid_lines);
+        assert_eq!(MAX_ERRORS, stats.errors.len());
+    }
+}
@@ -2,9 +2,122 @@
 //!
 //! Usage:
 //!     simplify_html < article.html > simplified.html
-use std::io::{stdin, stdout, Read, Write};
+//!     simplify_html article.html simplified.html
+//!     simplify_html articles/ [simplified/]
+//!     simplify_html --pretty < article.html
+//!     simplify_html --diff before.html after.html
+//!     simplify_html --list-languages
+use std::{
+    fs,
+    io::{stdin, stdout, Read, Write},
+    path::{Path, PathBuf},
+};
 
-use om_wikiparser::html::simplify;
+use anyhow::Context;
+use clap::Parser;
+#[macro_use]
+extern crate log;
+
+use om_wikiparser::html::{
+    detect_lang, load_config_file, load_sections_file, pretty::pretty_print, section_counts,
+    simplify_with_report, ElementFilter, MathMode, RubyMode, SimplifyOptions, TableMode,
+};
+use scraper::Html;
+use similar::TextDiff;
+
+/// Simplify article html files, or stdin if no input is provided.
+#[derive(Parser)]
+struct Args {
+    /// Html file, or directory of `*.html` files, to simplify.
+    input: Option<PathBuf>,
+
+    /// File or directory to write the simplified html to.
+    ///
+    /// Defaults to stdout for a single file, and to `*.simplified.html` files next to the inputs for a directory.
+    output: Option<PathBuf>,
+
+    /// Language of the articles, detected from each file if not provided.
+    #[arg(long)]
+    lang: Option<String>,
+
+    /// Indent the output, with each block element on its own line.
+    #[arg(long)]
+    pretty: bool,
+
+    /// Path to a json file of additional css selectors of elements to remove or keep.
+    ///
+    /// See the `--selectors` option of `om-wikiparser`.
+    #[arg(long)]
+    selectors: Option<PathBuf>,
+
+    /// How to simplify MathML formulas.
+    #[arg(long, value_enum, default_value_t)]
+    math_mode: MathMode,
+
+    /// How to simplify tables.
+    #[arg(long, value_enum, default_value_t)]
+    table_mode: TableMode,
+
+    /// How to simplify ruby pronunciation annotations.
+    #[arg(long, value_enum, default_value_t)]
+    ruby_mode: RubyMode,
+
+    /// Remove headers deeper than this level (e.g. `3` removes `h4` through `h7`), keeping the contents of their sections.
+    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(1..=7))]
+    max_header_level: u8,
+
+    /// Path to a json file to use instead of the built-in `article_processing_config.json`.
+    ///
+    /// Titles that aren't NFC-normalized are normalized with a warning.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Path to a json file of additional section titles to remove for each language.
+    ///
+    /// The file has the same structure as `article_processing_config.json`, e.g. `{"sections_to_remove": {"en": ["Gallery"]}}`.
+    #[arg(long)]
+    sections_file: Option<PathBuf>,
+
+    /// Remove bidirectional text control characters, like left-to-right marks, from text.
+    ///
+    /// Zero-width spaces and soft hyphens are always removed.
+    #[arg(long)]
+    strip_bidi: bool,
+
+    /// Keep the whitespace of `code` elements, and the indentation of syntax-highlighted code blocks.
+    #[arg(long)]
+    keep_code: bool,
+
+    /// Write well-formed XHTML, with self-closed void elements and a single root element, for XML tools.
+    #[arg(long, conflicts_with = "pretty")]
+    xhtml: bool,
+
+    /// Replace removed images with their `alt` text in brackets, e.g. `[A castle]`.
+    #[arg(long)]
+    image_alt_text: bool,
+
+    /// Keep whitespace in text as is, instead of collapsing it.
+    #[arg(long)]
+    keep_whitespace: bool,
+
+    /// Log how many elements each simplification step removed.
+    #[arg(long)]
+    report: bool,
+
+    /// Print a unified diff from this file to the input, after simplifying and pretty-printing both, instead of writing the output.
+    ///
+    /// Use this to compare an article across two versions or configurations of the simplification.
+    #[arg(long, requires = "input", conflicts_with_all = ["output", "xhtml"])]
+    diff: Option<PathBuf>,
+
+    /// Print each language with sections to remove, and the number of section titles, instead of simplifying.
+    ///
+    /// Includes the `--config` and `--sections-file` titles.
+    #[arg(long, conflicts_with_all = ["input", "diff"])]
+    list_languages: bool,
+}
+
+const SIMPLIFIED_EXTENSION: &str = "simplified.html";
 
 fn main() -> anyhow::Result<()> {
     env_logger::Builder::new()
@@ -12,12 +125,230 @@ fn main() -> anyhow::Result<()> {
         .parse_default_env()
         .try_init()?;
 
-    let mut input = String::new();
-    stdin().read_to_string(&mut input)?;
+    let args = Args::parse();
+
+    let mut sections_to_remove = match &args.config {
+        Some(path) => load_config_file(path)?,
+        None => Default::default(),
+    };
+    if let Some(path) = &args.sections_file {
+        for (lang, titles) in load_sections_file(path)? {
+            sections_to_remove.entry(lang).or_default().extend(titles);
+        }
+    }
+
+    let options = Options {
+        lang: args.lang.as_deref(),
+        pretty: args.pretty,
+        report: args.report,
+        simplify: SimplifyOptions {
+            elements: match &args.selectors {
+                Some(path) => ElementFilter::from_file(path)?,
+                None => ElementFilter::default(),
+            },
+            math_mode: args.math_mode,
+            table_mode: args.table_mode,
+            ruby_mode: args.ruby_mode,
+            sections_to_remove,
+            builtin_sections: args.config.is_none(),
+            max_header_level: args.max_header_level,
+            strip_bidi: args.strip_bidi,
+            keep_code: args.keep_code,
+            xhtml: args.xhtml,
+            image_alt_text: args.image_alt_text,
+            collapse_whitespace: !args.keep_whitespace,
+            ..Default::default()
+        },
+    };
+
+    if args.list_languages {
+        for (lang, count) in section_counts(&options.simplify) {
+            println!("{lang}\t{count}");
+        }
+        return Ok(());
+    }
+
+    if let (Some(other), Some(input)) = (&args.diff, &args.input) {
+        let options = Options {
+            pretty: true,
+            ..options
+        };
+        let old = simplify_file(other, &options)?;
+        let new = simplify_file(input, &options)?;
+        print!(
+            "{}",
+            diff(
+                &old,
+                &new,
+                &other.to_string_lossy(),
+                &input.to_string_lossy()
+            )
+        );
+        return Ok(());
+    }
+
+    match &args.input {
+        None => {
+            let mut input = String::new();
+            stdin().read_to_string(&mut input)?;
+
+            let output = simplify_logged(&input, &options, "stdin");
 
-    let output = simplify(&input, "en");
+            stdout().write_all(output.as_bytes())?;
+        }
+        Some(input) if input.is_dir() => simplify_dir(input, args.output.as_deref(), &options)?,
+        Some(input) => {
+            let output = simplify_file(input, &options)?;
 
-    stdout().write_all(output.as_bytes())?;
+            match &args.output {
+                Some(path) => {
+                    fs::write(path, output).with_context(|| format!("writing {:?}", path))?
+                }
+                None => stdout().write_all(output.as_bytes())?,
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// Read and simplify the html file at `path`.
+fn simplify_file(path: &Path, options: &Options) -> anyhow::Result<String> {
+    let html = fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+    Ok(simplify_logged(&html, options, &path.to_string_lossy()))
+}
+
+/// Unified diff of the lines from `old` to `new`, empty if they are the same.
+fn diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/// Simplify each `*.html` file in `input`, writing to `output` if provided or next to the original otherwise.
+fn simplify_dir(input: &Path, output: Option<&Path>, options: &Options) -> anyhow::Result<()> {
+    if let Some(output) = output {
+        fs::create_dir_all(output).with_context(|| format!("creating {:?}", output))?;
+    }
+
+    for entry in fs::read_dir(input).with_context(|| format!("reading {:?}", input))? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if !name.ends_with(".html") || name.ends_with(SIMPLIFIED_EXTENSION) {
+            continue;
+        }
+
+        let html = fs::read_to_string(&path).with_context(|| format!("reading {:?}", path))?;
+        let simplified = simplify_logged(&html, options, &name);
+
+        let target = match output {
+            Some(dir) => dir.join(path.file_name().unwrap()),
+            None => path.with_extension(SIMPLIFIED_EXTENSION),
+        };
+        fs::write(&target, simplified).with_context(|| format!("writing {:?}", target))?;
+    }
+
+    Ok(())
+}
+
+/// Settings shared by all simplified files.
+#[derive(Default)]
+struct Options<'a> {
+    lang: Option<&'a str>,
+    pretty: bool,
+    report: bool,
+    simplify: SimplifyOptions,
+}
+
+/// Simplify `html`, detecting the language if not provided, and log the size reduction.
+fn simplify_logged(html: &str, options: &Options, name: &str) -> String {
+    let lang = options.lang.map(str::to_owned).unwrap_or_else(|| {
+        detect_lang(html).unwrap_or_else(|| {
+            warn!("Unable to detect language of {name}, using \"en\"");
+            "en".to_owned()
+        })
+    });
+
+    let (output, report) = simplify_with_report(html, &lang, &options.simplify);
+    if options.report {
+        info!("{name}: {report:?}");
+    }
+
+    let (before, after) = (html.len(), output.len());
+    info!(
+        "Simplified {name} ({lang}) from {before} to {after} bytes ({:.0}%)",
+        100.0 * after as f64 / before.max(1) as f64
+    );
+
+    if options.pretty {
+        pretty_print(&Html::parse_document(&output))
+    } else {
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use om_wikiparser::html::simplify;
+
+    #[test]
+    fn simplify_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let en = r#"<html><head><base href="//en.wikipedia.org/wiki/"/></head><body><h2>See also</h2><p>Removed</p></body></html>"#;
+        let de = r#"<html><head><base href="//de.wikipedia.org/wiki/"/></head><body><h2>Siehe auch</h2><p>Removed</p></body></html>"#;
+        fs::write(dir.path().join("en.html"), en).unwrap();
+        fs::write(dir.path().join("de.html"), de).unwrap();
+        fs::write(dir.path().join("notes.txt"), "Not html").unwrap();
+
+        super::simplify_dir(dir.path(), None, &Options::default()).unwrap();
+
+        for (name, html, lang) in [("en", en, "en"), ("de", de, "de")] {
+            let simplified = fs::read_to_string(dir.path().join(format!("{name}.simplified.html")));
+            assert_eq!(simplify(html, lang), simplified.unwrap());
+        }
+        assert!(!dir.path().join("notes.simplified.html").exists());
+
+        // Rerunning should not simplify the outputs again.
+        super::simplify_dir(dir.path(), None, &Options::default()).unwrap();
+        assert!(!dir.path().join("en.simplified.simplified.html").exists());
+    }
+
+    #[test]
+    fn diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let before = dir.path().join("before.html");
+        let after = dir.path().join("after.html");
+        let copy = dir.path().join("copy.html");
+        let html = "<html><head></head><body><h2>History</h2><p>Founded in 1237.</p><p>Unchanged.</p></body></html>";
+        fs::write(&before, html).unwrap();
+        fs::write(&copy, html).unwrap();
+        fs::write(&after, html.replace("1237", "1244")).unwrap();
+
+        let options = Options {
+            lang: Some("en"),
+            pretty: true,
+            ..Default::default()
+        };
+        let simplified = |path| simplify_file(path, &options).unwrap();
+
+        assert_eq!(
+            "",
+            super::diff(&simplified(&before), &simplified(&copy), "before", "copy")
+        );
+
+        let diff = super::diff(&simplified(&before), &simplified(&after), "before", "after");
+        assert!(diff.starts_with("--- before\n+++ after\n"), "{diff}");
+        let changed: Vec<_> = diff
+            .lines()
+            .skip(2)
+            .filter(|l| l.starts_with(['-', '+']))
+            .map(|l| (&l[..1], l[1..].trim()))
+            .collect();
+        assert_eq!(
+            vec![("-", "Founded in 1237."), ("+", "Founded in 1244.")],
+            changed
+        );
+    }
+}
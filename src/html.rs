@@ -1,9 +1,27 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    fs, iter,
+    path::Path,
+};
 
+use anyhow::{anyhow, bail, Context};
 use ego_tree::NodeId;
+use html5ever::{local_name, namespace_url, ns, QualName};
 use once_cell::sync::Lazy;
-use scraper::{ElementRef, Html, Selector};
-use serde::Deserialize;
+use scraper::{
+    node::{Element, Text},
+    ElementRef, Html, Node, Selector,
+};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use url::Url;
+
+use crate::wm::wikipedia_edition;
+
+pub mod pretty;
+pub mod xhtml;
 
 #[derive(Debug, Deserialize)]
 struct Config<'a> {
@@ -19,24 +37,934 @@ static CONFIG: Lazy<Config<'static>> = Lazy::new(|| {
     .expect("\"article_processing_config.json\" is either invalid json or the wrong structure")
 });
 
+/// Load additional section titles to remove from a json file with the same structure as `article_processing_config.json`.
+///
+/// Titles are compared exactly, so they must be NFC-normalized like the article html.
+pub fn load_sections_file(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    #[derive(Deserialize)]
+    struct SectionsFile {
+        sections_to_remove: BTreeMap<String, BTreeSet<String>>,
+    }
+
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+    let file: SectionsFile =
+        serde_json::from_str(&contents).with_context(|| format!("parsing {:?}", path))?;
+
+    for (lang, titles) in &file.sections_to_remove {
+        if let Some(title) = titles.iter().find(|t| !is_nfc(t)) {
+            bail!("section title {title:?} for lang {lang:?} in {path:?} is not NFC-normalized");
+        }
+    }
+
+    Ok(file.sections_to_remove)
+}
+
+/// Load a replacement for the built-in `article_processing_config.json`.
+///
+/// Use the titles with [SimplifyOptions::builtin_sections] disabled.
+/// Unlike [load_sections_file], titles that aren't NFC-normalized are normalized with a warning.
+pub fn load_config_file(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?;
+    let config: Config =
+        serde_json::from_str(&contents).with_context(|| format!("parsing {:?}", path))?;
+
+    let mut sections_to_remove = BTreeMap::new();
+    for (lang, titles) in config.sections_to_remove {
+        let titles = titles
+            .into_iter()
+            .map(|title| {
+                if !is_nfc(title) {
+                    warn!(
+                        "Normalizing section title {title:?} for lang {lang:?} in {path:?} to NFC"
+                    );
+                }
+                title.nfc().collect()
+            })
+            .collect();
+        sections_to_remove.insert(lang.to_owned(), titles);
+    }
+
+    Ok(sections_to_remove)
+}
+
+/// The number of section titles removed for each language with `options`.
+///
+/// Languages without any sections configured still keep sections like "See also" and "References".
+pub fn section_counts(options: &SimplifyOptions) -> BTreeMap<String, usize> {
+    let mut titles: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    if options.builtin_sections {
+        for (lang, sections) in &CONFIG.sections_to_remove {
+            titles.entry(lang).or_default().extend(sections);
+        }
+    }
+    for (lang, sections) in &options.sections_to_remove {
+        titles
+            .entry(lang)
+            .or_default()
+            .extend(sections.iter().map(String::as_str));
+    }
+
+    titles
+        .into_iter()
+        .map(|(lang, sections)| (lang.to_owned(), sections.len()))
+        .collect()
+}
+
 static HEADERS: Lazy<Selector> =
     Lazy::new(|| Selector::parse("h1, h2, h3, h4, h5, h6, h7").unwrap());
 
+static BASE: Lazy<Selector> = Lazy::new(|| Selector::parse("head > base[href]").unwrap());
+
+/// Get the absolute base url of an article (`<base href="//lang.wikipedia.org/wiki/">`).
+fn base_url(document: &Html) -> Option<Url> {
+    let href = document.select(&BASE).next()?.value().attr("href")?;
+
+    // Base is usually protocol-relative.
+    Url::parse("https://wikipedia.org/").ok()?.join(href).ok()
+}
+
+/// Elements that declare the language of a document, after the base url.
+static LANG_HINTS: Lazy<[(Selector, &str); 3]> = Lazy::new(|| {
+    [
+        (Selector::parse("html[lang]").unwrap(), "lang"),
+        (
+            Selector::parse(r#"meta[http-equiv="content-language" i][content]"#).unwrap(),
+            "content",
+        ),
+        (
+            Selector::parse(r#"meta[name="language" i][content]"#).unwrap(),
+            "content",
+        ),
+    ]
+});
+
+/// Detect the language of an article from its base url (`<base href="//lang.wikipedia.org/wiki/">`).
+///
+/// If there is no base url, falls back to the `<html lang>` attribute and then `<meta>` language hints.
+pub fn detect_lang(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    detect_document_lang(&document)
+}
+
+fn detect_document_lang(document: &Html) -> Option<String> {
+    lang_from_base(document).or_else(|| {
+        LANG_HINTS.iter().find_map(|(selector, attr)| {
+            let lang = document.select(selector).next()?.value().attr(attr)?.trim();
+            if lang.is_empty() {
+                return None;
+            }
+            // Map variants like `zh-Hans` to the edition they are in, like the urls do.
+            Some(match wikipedia_edition(lang) {
+                Some(edition) => edition.to_owned(),
+                None => lang.to_lowercase(),
+            })
+        })
+    })
+}
+
+fn lang_from_base(document: &Html) -> Option<String> {
+    let url = base_url(document)?;
+    let (lang, host) = url.host_str()?.split_once('.')?;
+    if host != "wikipedia.org" {
+        return None;
+    }
+
+    Some(lang.to_owned())
+}
+
+/// A header of an article, from [extract_toc].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TocEntry {
+    /// Header level, `1` for `h1` through `7` for `h7`.
+    pub level: u8,
+    /// Text of the header, with whitespace collapsed.
+    pub text: String,
+    /// The `id` of the header element that can be linked to, if it has one.
+    pub id: Option<String>,
+}
+
+/// Get the outline of an article from its headers, in document order.
+///
+/// Nesting is represented by the [TocEntry::level] of consecutive entries.
+/// Run this on the original article, as [simplify] may remove sections.
+pub fn extract_toc(document: &Html) -> Vec<TocEntry> {
+    document
+        .select(&HEADERS)
+        .filter_map(|header| {
+            let level = header_level(&header)?;
+            let text = header
+                .text()
+                .flat_map(str::split_whitespace)
+                .collect::<Vec<_>>()
+                .join(" ");
+            let id = header.value().id().map(str::to_owned);
+            Some(TocEntry { level, text, id })
+        })
+        .collect()
+}
+
+/// Places to look for the article's main image, in order of preference.
+static THUMBNAIL_CONTAINERS: Lazy<[Selector; 2]> = Lazy::new(|| {
+    [
+        Selector::parse(".infobox img").unwrap(),
+        Selector::parse(r#"section[data-mw-section-id="0"] img"#).unwrap(),
+    ]
+});
+
+/// Classes of images, or their containers, that are icons rather than illustrations.
+const ICON_CLASSES: &[&str] = &["noviewer", "flagicon", "mw-kartographer-map"];
+
+/// Images smaller than this in either dimension are assumed to be icons.
+const MIN_THUMBNAIL_SIZE: u32 = 50;
+
+/// Find the main image of an article in its infobox or lead section, as an absolute url.
+///
+/// Icons, like flags and small symbols, are skipped.
+/// Run this on the original article, as [simplify] removes images.
+pub fn extract_thumbnail(document: &Html) -> Option<String> {
+    let base = base_url(document).or_else(|| Url::parse("https://wikipedia.org/").ok())?;
+
+    let img = THUMBNAIL_CONTAINERS
+        .iter()
+        .flat_map(|selector| document.select(selector))
+        .find(|img| !is_icon(img))?;
+    let src = img.value().attr("src")?;
+
+    base.join(src).ok().map(String::from)
+}
+
+fn is_icon(img: &ElementRef) -> bool {
+    let is_small = ["width", "height"].iter().any(|dimension| {
+        img.value()
+            .attr(dimension)
+            .and_then(|size| size.parse::<u32>().ok())
+            .map(|size| size < MIN_THUMBNAIL_SIZE)
+            .unwrap_or_default()
+    });
+
+    let has_icon_class = std::iter::once(*img)
+        .chain(img.ancestors().filter_map(ElementRef::wrap))
+        .any(|el| el.value().classes().any(|c| ICON_CLASSES.contains(&c)));
+
+    is_small || has_icon_class
+}
+
+static COORDINATES: Lazy<Selector> = Lazy::new(|| Selector::parse(".geo").unwrap());
+
+/// Find the coordinates of the subject of an article, as `(latitude, longitude)` in decimal degrees.
+///
+/// Uses the first machine-readable `<span class="geo">lat; lon</span>` added by the coordinate templates.
+pub fn extract_coordinates(document: &Html) -> Option<(f64, f64)> {
+    let text: String = document.select(&COORDINATES).next()?.text().collect();
+    let (lat, lon) = text.split_once(';')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return None;
+    }
+
+    Some((lat, lon))
+}
+
+static REDIRECT: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(r#"link[rel="mw:PageProp/redirect"][href]"#).unwrap());
+
+/// Get the title a redirect page points to, or `None` if it is not a redirect.
+fn redirect_target(document: &Html) -> Option<String> {
+    let href = document.select(&REDIRECT).next()?.value().attr("href")?;
+    let title = href.strip_prefix("./").unwrap_or(href);
+    let title = urlencoding::decode(title).ok()?;
+
+    Some(title.replace('_', " "))
+}
+
+/// A simplified article and the information extracted from it by [extract_article].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Article {
+    pub lang: String,
+    /// The title of the article this page redirects to, if it is a redirect.
+    pub is_redirect: Option<String>,
+    pub simplified_html: String,
+    /// Number of characters of text in the simplified html.
+    pub text_len: usize,
+    /// See [extract_coordinates].
+    pub coordinates: Option<(f64, f64)>,
+    /// See [extract_thumbnail].
+    pub thumbnail: Option<String>,
+    /// See [classify].
+    pub kind: PageKind,
+}
+
+/// Simplify a single article with `options` and extract its metadata.
+///
+/// The language is `lang_hint` if provided, otherwise it is detected with [detect_lang].
+pub fn extract_article(
+    html: &str,
+    lang_hint: Option<&str>,
+    options: &SimplifyOptions,
+) -> anyhow::Result<Article> {
+    let document = Html::parse_document(html);
+
+    let lang = match lang_hint {
+        Some(lang) => lang.to_owned(),
+        None => detect_document_lang(&document)
+            .context("unable to detect the language of the article, and no hint was provided")?,
+    };
+
+    // The metadata is extracted from the original document.
+    let (simplified, _) = simplify_document(document.clone(), &lang, options);
+    let text_len = simplified
+        .tree
+        .root()
+        .descendants()
+        .filter_map(|node| node.value().as_text())
+        .map(|t| t.chars().count())
+        .sum();
+    let simplified_html = serialize(&simplified, options);
+
+    Ok(Article {
+        kind: classify(&document),
+        is_redirect: redirect_target(&document),
+        coordinates: extract_coordinates(&document),
+        thumbnail: extract_thumbnail(&document),
+        lang,
+        simplified_html,
+        text_len,
+    })
+}
+
+/// Get the level of a header element, `1` for `h1` through `7` for `h7`.
+fn header_level(header: &ElementRef) -> Option<u8> {
+    header.value().name().strip_prefix('h')?.parse().ok()
+}
+
+/// Markers added by the `__DISAMBIG__` magic word and the disambiguation templates.
+static DISAMBIGUATION: Lazy<Selector> = Lazy::new(|| {
+    Selector::parse(
+        r#"meta[property="mw:PageProp/disambiguation"], #disambigbox, .dmbox.dmbox-disambig"#,
+    )
+    .unwrap()
+});
+
+/// Check if `document` is a disambiguation page.
+///
+/// Only the markers MediaWiki and the disambiguation templates add are used,
+/// so articles that merely link to or mention disambiguation pages are not matched.
+pub fn is_disambiguation(document: &Html) -> bool {
+    document.select(&DISAMBIGUATION).next().is_some()
+}
+
+/// Kind of page, for filtering articles by quality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageKind {
+    Article,
+    /// A short article, see [classify].
+    Stub,
+    Disambiguation,
+    Redirect,
+}
+
+impl Display for PageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            PageKind::Article => "article",
+            PageKind::Stub => "stub",
+            PageKind::Disambiguation => "disambiguation",
+            PageKind::Redirect => "redirect",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// Markers added by the stub templates, e.g. `{{Germany-geo-stub}}`.
+static STUB: Lazy<Selector> = Lazy::new(|| Selector::parse(".asbox, .stub").unwrap());
+
+/// Articles with fewer characters of text in paragraphs than this are stubs.
+const STUB_PROSE_LEN: usize = 300;
+
+/// Classify the page in `document`.
+///
+/// Redirects are detected with [redirect_target], and disambiguation pages like [is_disambiguation].
+/// Articles are stubs if they are marked with a stub template, or if their paragraphs have fewer than [STUB_PROSE_LEN] characters.
+pub fn classify(document: &Html) -> PageKind {
+    if redirect_target(document).is_some() {
+        return PageKind::Redirect;
+    }
+    if document.select(&DISAMBIGUATION).next().is_some() {
+        return PageKind::Disambiguation;
+    }
+
+    let prose_len: usize = document
+        .select(&PARAGRAPH)
+        .flat_map(|p| p.text())
+        .map(|t| t.trim().chars().count())
+        .sum();
+    if prose_len < STUB_PROSE_LEN || document.select(&STUB).next().is_some() {
+        return PageKind::Stub;
+    }
+
+    PageKind::Article
+}
+
+/// Check if `document` is a redirect page, see [redirect_target].
+pub fn is_redirect(document: &Html) -> bool {
+    redirect_target(document).is_some()
+}
+
+/// Css selectors of elements to remove from articles.
+const ELEMENT_DENY_LIST: &[&str] = &[
+    "img",
+    "picture",
+    // Navigation templates at the end of articles.
+    ".navbox",
+    ".vertical-navbox",
+    ".navigation-box",
+    // "Not to be confused with..." and "Main article:" notes.
+    ".hatnote",
+    "div[role=\"note\"]",
+    // Placeholders for templates without content.
+    ".mw-empty-elt",
+    // Citations, see also [is_citation].
+    "sup.reference",
+    // Page properties and metadata in the body, like `<link rel="mw:PageProp/redirect">`.
+    // They are removed after [redirect_target] checks the original document.
+    "link",
+    "meta",
+];
+
+/// Css selectors of elements to keep, even if they match [ELEMENT_DENY_LIST].
+const ELEMENT_ALLOW_LIST: &[&str] = &[];
+
+/// Elements to remove from articles, and exceptions to keep.
+#[derive(Debug, Clone)]
+pub struct ElementFilter {
+    deny: Selector,
+    allow: Option<Selector>,
+}
+
+/// Additional css selectors loaded by [ElementFilter::from_file].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SelectorsFile {
+    deny: Vec<String>,
+    allow: Vec<String>,
+}
+
+impl ElementFilter {
+    /// Merge the `deny` and `allow` css selectors with the built-in lists.
+    pub fn new(
+        deny: impl IntoIterator<Item = impl AsRef<str>>,
+        allow: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> anyhow::Result<Self> {
+        let deny = compile_selectors(ELEMENT_DENY_LIST, deny)?
+            .ok_or_else(|| anyhow!("deny list cannot be empty"))?;
+        let allow = compile_selectors(ELEMENT_ALLOW_LIST, allow)?;
+
+        Ok(Self { deny, allow })
+    }
+
+    /// Load additional css selectors from a json file of the form `{"deny": [...], "allow": [...]}`.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("reading selectors {:?}", path))?;
+        let file: SelectorsFile = serde_json::from_str(&contents)
+            .with_context(|| format!("parsing selectors {:?}", path))?;
+
+        Self::new(file.deny, file.allow).with_context(|| format!("loading selectors {:?}", path))
+    }
+
+    fn should_remove(&self, el: &ElementRef) -> bool {
+        self.deny.matches(el)
+            && !self
+                .allow
+                .as_ref()
+                .map(|allow| allow.matches(el))
+                .unwrap_or_default()
+    }
+}
+
+impl Default for ElementFilter {
+    fn default() -> Self {
+        Self::new(None::<&str>, None::<&str>).expect("built-in selectors should be valid")
+    }
+}
+
+/// Combine selectors into a single selector list, checking each is valid.
+fn compile_selectors(
+    builtin: &[&str],
+    extra: impl IntoIterator<Item = impl AsRef<str>>,
+) -> anyhow::Result<Option<Selector>> {
+    let mut selectors: Vec<String> = builtin.iter().map(|s| s.to_string()).collect();
+    selectors.extend(extra.into_iter().map(|s| s.as_ref().to_owned()));
+    if selectors.is_empty() {
+        return Ok(None);
+    }
+
+    for selector in &selectors {
+        Selector::parse(selector).map_err(|e| anyhow!("invalid selector {selector:?}: {e}"))?;
+    }
+
+    let combined = selectors.join(", ");
+    let selector = Selector::parse(&combined)
+        .map_err(|e| anyhow!("invalid selector list {combined:?}: {e}"))?;
+    Ok(Some(selector))
+}
+
+/// Options to customize [simplify_with].
+#[derive(Debug, Clone)]
+pub struct SimplifyOptions {
+    pub elements: ElementFilter,
+    /// Never remove definition lists (`dl`, `dt`, `dd`) and `blockquote`s with the [ElementFilter].
+    pub keep_definition_lists: bool,
+    pub math_mode: MathMode,
+    pub table_mode: TableMode,
+    pub ruby_mode: RubyMode,
+    /// Collapse runs of whitespace in text to a single space, and remove it next to block elements.
+    ///
+    /// Text in `pre` and `textarea` elements is not changed.
+    pub collapse_whitespace: bool,
+    /// Remove headers deeper than this level, keeping the contents of their sections.
+    ///
+    /// Sections are removed by title before this is applied, so it doesn't affect which sections are removed.
+    /// The default of `7` keeps all headers.
+    pub max_header_level: u8,
+    /// Titles of sections to remove for each language, in addition to the built-in ones.
+    ///
+    /// See [load_sections_file].
+    pub sections_to_remove: BTreeMap<String, BTreeSet<String>>,
+    /// Also remove the sections in the built-in `article_processing_config.json`.
+    ///
+    /// Disable this to replace them with a file loaded by [load_config_file].
+    pub builtin_sections: bool,
+    /// Invisible characters to remove from text, e.g. zero-width spaces and soft hyphens.
+    pub strip_chars: Vec<char>,
+    /// Also remove bidirectional text control characters, like left-to-right marks.
+    ///
+    /// These are kept by default because they can be needed to display mixed-direction text correctly.
+    pub strip_bidi: bool,
+    /// Keep the formatting of code in `code` and `pre` elements.
+    ///
+    /// Whitespace in inline `code` isn't collapsed, and whitespace-only elements in code blocks,
+    /// like the indentation of syntax-highlighted lines, aren't removed.
+    pub keep_code: bool,
+    /// Serialize the output as well-formed XML, see [xhtml::to_xhtml].
+    pub xhtml: bool,
+    /// Replace removed images with their `alt` text in brackets, e.g. `[A castle]`.
+    ///
+    /// Images without an `alt`, or with an empty one for decorative images, are removed as usual.
+    pub image_alt_text: bool,
+    /// Names of elements to remove the `class` attribute from.
+    ///
+    /// The `style` attribute is always removed from all elements, and other attributes, like `id`, are kept.
+    pub strip_class: Vec<String>,
+}
+
+/// Default [SimplifyOptions::strip_chars].
+pub const INVISIBLE_CHARS: &[char] = &[
+    '\u{200B}', // Zero width space
+    '\u{200C}', // Zero width non-joiner
+    '\u{200D}', // Zero width joiner
+    '\u{00AD}', // Soft hyphen
+    '\u{FEFF}', // Zero width no-break space/byte order mark
+];
+
+/// Default [SimplifyOptions::strip_class].
+pub const CLASS_STRIPPED_ELEMENTS: &[&str] = &["span"];
+
+/// Bidirectional marks, embeddings, overrides, and isolates removed by [SimplifyOptions::strip_bidi].
+const BIDI_CONTROL_CHARS: &[char] = &[
+    '\u{061C}', '\u{200E}', '\u{200F}', '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}',
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        Self {
+            elements: Default::default(),
+            keep_definition_lists: true,
+            math_mode: Default::default(),
+            table_mode: Default::default(),
+            ruby_mode: Default::default(),
+            collapse_whitespace: true,
+            max_header_level: 7,
+            sections_to_remove: Default::default(),
+            builtin_sections: true,
+            strip_chars: INVISIBLE_CHARS.to_vec(),
+            strip_bidi: false,
+            keep_code: false,
+            xhtml: false,
+            image_alt_text: false,
+            strip_class: CLASS_STRIPPED_ELEMENTS
+                .iter()
+                .map(|&name| name.to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// How to simplify MathML `<math>` formulas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MathMode {
+    /// Keep the MathML intact, including the `alttext` LaTeX source.
+    #[default]
+    Keep,
+    /// Replace the MathML with its `alttext` as plain text.
+    #[value(name = "alttext")]
+    AltText,
+    /// Remove formulas entirely.
+    Drop,
+}
+
+static MATH: Lazy<Selector> = Lazy::new(|| Selector::parse("math").unwrap());
+
+/// Replace or remove `<math>` elements according to `mode`.
+fn simplify_math(document: &mut Html, mode: MathMode) {
+    if mode == MathMode::Keep {
+        return;
+    }
+
+    // Replace the whole formula, including Parsoid's wrapper with the hidden MathML and fallback image.
+    let formulas: Vec<_> = document
+        .select(&MATH)
+        .map(|el| {
+            let wrapper = el
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .find(|a| a.value().classes().any(|c| c == "mwe-math-element"));
+            let alttext = el.value().attr("alttext").map(latex_text);
+            (wrapper.unwrap_or(el).id(), alttext)
+        })
+        .collect();
+
+    for (id, alttext) in formulas {
+        let Some(mut node) = document.tree.get_mut(id) else {
+            continue;
+        };
+        if let (MathMode::AltText, Some(text)) = (mode, alttext) {
+            node.insert_before(Node::Text(Text { text: text.into() }));
+        }
+        node.detach();
+    }
+}
+
+/// Strip the `{\displaystyle ...}` wrapper that MediaWiki adds to formula sources.
+fn latex_text(alttext: &str) -> String {
+    let alttext = alttext.trim();
+    alttext
+        .strip_prefix("{\\displaystyle")
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(alttext)
+        .trim()
+        .to_owned()
+}
+
+/// How to simplify `<table>` elements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TableMode {
+    /// Keep the table html.
+    #[default]
+    #[value(name = "keep_html")]
+    KeepHtml,
+    /// Replace tables with their rows as tab-separated plain text in a `pre`, for readers that can't display tables.
+    ///
+    /// Header rows are followed by a line of dashes.
+    /// Cells spanning multiple columns are followed by empty ones, and cells spanning multiple rows are repeated in each.
+    Text,
+    /// Remove tables entirely.
+    Drop,
+}
+
+static TABLE: Lazy<Selector> = Lazy::new(|| Selector::parse("table").unwrap());
+
+/// Largest `colspan` or `rowspan` used by [TableMode::Text], to limit the output of malformed tables.
+const MAX_CELL_SPAN: usize = 100;
+
+/// Replace or remove `<table>` elements according to `mode`.
+fn simplify_tables(document: &mut Html, mode: TableMode) {
+    if mode == TableMode::KeepHtml {
+        return;
+    }
+
+    // Nested tables are included in the text of their cells.
+    let tables: Vec<_> = select_attached(document, &TABLE)
+        .filter(|table| {
+            !table
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|a| a.value().name() == "table")
+        })
+        .map(|table| {
+            (
+                table.id(),
+                (mode == TableMode::Text).then(|| table_text(&table)),
+            )
+        })
+        .collect();
+
+    for (id, text) in tables {
+        let mut node = document.tree.get_mut(id).unwrap();
+        if let Some(text) = text {
+            let pre = Element::new(
+                QualName::new(None, ns!(html), local_name!("pre")),
+                Vec::new(),
+            );
+            node.insert_before(Node::Element(pre))
+                .append(Node::Text(Text { text: text.into() }));
+        }
+        node.detach();
+    }
+}
+
+/// The caption and rows of `table` as lines of tab-separated cells, see [TableMode::Text].
+fn table_text(table: &ElementRef) -> String {
+    let mut lines = Vec::new();
+    if let Some(caption) = table
+        .children()
+        .filter_map(ElementRef::wrap)
+        .find(|el| el.value().name() == "caption")
+    {
+        lines.push(cell_text(&caption));
+    }
+
+    // The remaining rows and text of cells spanning multiple rows, by column.
+    let mut row_spans: Vec<Option<(usize, String)>> = Vec::new();
+    for row in table_rows(table) {
+        let mut cells = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|el| matches!(el.value().name(), "td" | "th"));
+        let mut line = Vec::new();
+        let mut is_header = true;
+        loop {
+            let column = line.len();
+            if let Some(Some((remaining, text))) = row_spans.get_mut(column) {
+                line.push(text.clone());
+                *remaining -= 1;
+                if *remaining == 0 {
+                    row_spans[column] = None;
+                }
+                continue;
+            }
+
+            let Some(cell) = cells.next() else {
+                if row_spans.iter().skip(column).any(Option::is_some) {
+                    line.push(String::new());
+                    continue;
+                }
+                break;
+            };
+            is_header &= cell.value().name() == "th";
+            let text = cell_text(&cell);
+            let rowspan = cell_span(&cell, "rowspan");
+            for i in 0..cell_span(&cell, "colspan") {
+                let text = if i == 0 { text.clone() } else { String::new() };
+                if rowspan > 1 {
+                    let column = line.len();
+                    if row_spans.len() <= column {
+                        row_spans.resize(column + 1, None);
+                    }
+                    row_spans[column] = Some((rowspan - 1, text.clone()));
+                }
+                line.push(text);
+            }
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+        lines.push(line.join("\t"));
+        if is_header {
+            let separator: Vec<_> = line
+                .iter()
+                .map(|cell| "-".repeat(cell.chars().count().max(3)))
+                .collect();
+            lines.push(separator.join("\t"));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// The `tr` rows of `table`, including those in `thead`, `tbody`, and `tfoot`, but not in nested tables.
+fn table_rows<'a>(table: &ElementRef<'a>) -> impl Iterator<Item = ElementRef<'a>> {
+    let is_row = |el: &ElementRef| el.value().name() == "tr";
+    table
+        .children()
+        .filter_map(ElementRef::wrap)
+        .flat_map(move |child| match child.value().name() {
+            "tr" => vec![child],
+            "thead" | "tbody" | "tfoot" => child
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(is_row)
+                .collect(),
+            _ => Vec::new(),
+        })
+}
+
+/// The text of a table cell, with runs of whitespace collapsed to a single space.
+fn cell_text(cell: &ElementRef) -> String {
+    let text: String = cell.text().collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The `colspan` or `rowspan` of a table cell, `1` if it is missing or invalid.
+fn cell_span(cell: &ElementRef, attr: &str) -> usize {
+    cell.value()
+        .attr(attr)
+        .and_then(|span| span.trim().parse().ok())
+        .unwrap_or(1)
+        .clamp(1, MAX_CELL_SPAN)
+}
+
+/// How to simplify `<ruby>` pronunciation annotations, like the furigana in Japanese articles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RubyMode {
+    /// Keep ruby elements in Japanese and Chinese articles, and simplify them like any other elsewhere.
+    #[default]
+    Auto,
+    /// Keep `ruby` elements and their annotations intact, without their attributes.
+    Keep,
+    /// Remove the `rt` annotations and `rp` fallback parentheses, keeping only the base text.
+    Flatten,
+}
+
+impl RubyMode {
+    /// Resolve [RubyMode::Auto] for articles in `lang`.
+    pub fn for_lang(self, lang: &str) -> Self {
+        let primary = lang.split('-').next().unwrap_or(lang);
+        match self {
+            Self::Auto if RUBY_LANGS.contains(&primary) => Self::Keep,
+            mode => mode,
+        }
+    }
+}
+
+/// Languages that use ruby annotations for pronunciation, kept by [RubyMode::Auto].
+///
+/// Regional editions like `zh-yue` are matched by their first subtag.
+pub const RUBY_LANGS: &[&str] = &["ja", "zh"];
+
+/// Elements that make up ruby annotations.
+const RUBY_ELEMENTS: &[&str] = &["ruby", "rb", "rt", "rtc", "rp"];
+
+static RUBY: Lazy<Selector> = Lazy::new(|| Selector::parse(&RUBY_ELEMENTS.join(", ")).unwrap());
+static RUBY_ANNOTATIONS: Lazy<Selector> = Lazy::new(|| Selector::parse("rt, rtc, rp").unwrap());
+static RUBY_BASES: Lazy<Selector> = Lazy::new(|| Selector::parse("ruby, rb").unwrap());
+
+/// Replace `<ruby>` elements with their base text for [RubyMode::Flatten].
+fn flatten_ruby(document: &mut Html) {
+    let annotations: Vec<_> = select_attached(document, &RUBY_ANNOTATIONS)
+        .map(|el| el.id())
+        .collect();
+    remove_ids(document, annotations);
+
+    let bases: Vec<_> = select_attached(document, &RUBY_BASES)
+        .map(|el| el.id())
+        .collect();
+    for id in bases {
+        replace_with_children(document, id);
+    }
+}
+
+/// Glossary and quotation elements kept by [SimplifyOptions::keep_definition_lists].
+static DEFINITION_LISTS: Lazy<Selector> =
+    Lazy::new(|| Selector::parse("dl, dt, dd, blockquote").unwrap());
+
+static DEFAULT_OPTIONS: Lazy<SimplifyOptions> = Lazy::new(SimplifyOptions::default);
+
 pub fn simplify(html: &str, lang: &str) -> String {
-    let mut document = Html::parse_document(html);
+    simplify_with(html, lang, &DEFAULT_OPTIONS)
+}
+
+/// Simplify `html` like [simplify], with custom options.
+pub fn simplify_with(html: &str, lang: &str, options: &SimplifyOptions) -> String {
+    simplify_with_report(html, lang, options).0
+}
+
+/// Number of nodes changed by each step of [simplify_with_report].
+///
+/// Nested nodes are counted individually, e.g. removing a denied `div` containing an `img` counts both.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SimplifyReport {
+    /// Headers of configured sections that were removed.
+    pub sections_removed: usize,
+    /// Nodes following the removed section headers.
+    pub section_nodes_removed: usize,
+    /// Headers deeper than [SimplifyOptions::max_header_level].
+    pub headers_removed: usize,
+    /// Elements matching the [ElementFilter].
+    pub denylist_removed: usize,
+    /// Elements without any text.
+    pub empty_removed: usize,
+    /// Citation superscripts without the `reference` class, see [is_citation].
+    pub citations_removed: usize,
+    /// Elements hidden by their style or class, see [is_hidden].
+    pub hidden_removed: usize,
+    /// Links that were replaced by their contents.
+    pub links_removed: usize,
+    /// Paragraphs left with only punctuation after the other steps.
+    pub empty_paragraphs_removed: usize,
+    /// List items left with only punctuation after the other steps, and lists left without items.
+    pub empty_lists_removed: usize,
+    /// The body has no text left after simplification, e.g. because all of its sections were removed.
+    pub no_text: bool,
+}
+
+/// Simplify `html` like [simplify_with], and count what was removed.
+pub fn simplify_with_report(
+    html: &str,
+    lang: &str,
+    options: &SimplifyOptions,
+) -> (String, SimplifyReport) {
+    let (document, report) = simplify_document(Html::parse_document(html), lang, options);
+    (serialize(&document, options), report)
+}
+
+/// Simplify the parsed `document` like [simplify_with_report].
+fn simplify_document(
+    mut document: Html,
+    lang: &str,
+    options: &SimplifyOptions,
+) -> (Html, SimplifyReport) {
+    let mut report = SimplifyReport::default();
+
+    // The `base` element is in the `head`, which is removed with the other empty elements.
+    let base = base_url(&document);
 
     let mut to_remove = Vec::new();
 
     // Remove configured sections and all trailing elements until next section.
 
-    if let Some(bad_sections) = CONFIG.sections_to_remove.get(lang) {
+    let bad_sections = CONFIG
+        .sections_to_remove
+        .get(lang)
+        .filter(|_| options.builtin_sections);
+    let custom_sections = options.sections_to_remove.get(lang);
+    let is_bad_section = |title: &str| {
+        bad_sections.map(|s| s.contains(title)).unwrap_or_default()
+            || custom_sections
+                .map(|s| s.contains(title))
+                .unwrap_or_default()
+    };
+
+    if bad_sections.is_some() || custom_sections.is_some() {
         for header in document.select(&HEADERS) {
             // TODO: Should this join all text nodes?
             let Some(title) = header.text().next() else {
                 continue
             };
 
-            if bad_sections.contains(&title.trim()) {
+            if is_bad_section(title.trim()) {
+                report.sections_removed += 1;
                 to_remove.push(header.id());
                 let header_level = header.value().name();
                 // Strip trailing nodes.
@@ -47,6 +975,7 @@ pub fn simplify(html: &str, lang: &str) -> String {
                             break;
                         }
                     }
+                    report.section_nodes_removed += 1;
                     to_remove.push(sibling.id());
                 }
             }
@@ -57,60 +986,568 @@ pub fn simplify(html: &str, lang: &str) -> String {
         warn!("No sections to remove configured for lang {lang:?}");
     }
 
+    for header in select_attached(&document, &HEADERS) {
+        if header_level(&header).unwrap_or_default() > options.max_header_level {
+            report.headers_removed += 1;
+            to_remove.push(header.id());
+        }
+    }
+    remove_ids(&mut document, to_remove.drain(..));
+
+    simplify_math(&mut document, options.math_mode);
+
+    let ruby_mode = options.ruby_mode.for_lang(lang);
+    if ruby_mode == RubyMode::Flatten {
+        flatten_ruby(&mut document);
+    }
+    let keep_ruby = ruby_mode == RubyMode::Keep;
+
+    let mut alt_texts = Vec::new();
     for el in document
         .root_element()
         .descendants()
         .filter_map(ElementRef::wrap)
     {
-        if is_image(&el) || is_empty_or_whitespace(&el) {
+        // Annotations can be short or empty, and are needed to read the base text.
+        if keep_ruby && RUBY.matches(&el) {
+            continue;
+        }
+        let is_kept = options.keep_definition_lists && DEFINITION_LISTS.matches(&el);
+        if !is_kept && options.elements.should_remove(&el) {
+            report.denylist_removed += 1;
+            if let Some(alt) = image_alt(&el).filter(|_| options.image_alt_text) {
+                alt_texts.push((el.id(), alt.to_owned()));
+            }
+            to_remove.push(el.id());
+        } else if is_citation(&el) {
+            report.citations_removed += 1;
+            to_remove.push(el.id());
+        } else if is_hidden(&el) {
+            report.hidden_removed += 1;
+            to_remove.push(el.id());
+        } else if is_empty_or_whitespace(&el)
+            && !contains_kept_media(&el, &options.elements)
+            && !(options.keep_code && is_code(*el))
+        {
+            report.empty_removed += 1;
             to_remove.push(el.id());
         }
     }
+    for (id, alt) in alt_texts {
+        let mut node = document.tree.get_mut(id).unwrap();
+        node.insert_before(Node::Text(Text {
+            text: format!("[{alt}]").into(),
+        }));
+    }
     remove_ids(&mut document, to_remove.drain(..));
 
-    remove_links(&mut document);
+    report.links_removed = remove_links(&mut document);
+    report.empty_paragraphs_removed = remove_empty_paragraphs(&mut document);
+    report.empty_lists_removed = remove_empty_lists(&mut document);
+    simplify_tables(&mut document, options.table_mode);
 
-    document.html()
-}
+    let mut strip_chars = options.strip_chars.clone();
+    if options.strip_bidi {
+        strip_chars.extend(BIDI_CONTROL_CHARS);
+    }
+    strip_text(&mut document, &strip_chars);
+    remove_attrs(&mut document, &options.strip_class, keep_ruby);
 
-fn remove_ids(document: &mut Html, ids: impl IntoIterator<Item = NodeId>) {
-    for id in ids {
-        if let Some(mut node) = document.tree.get_mut(id) {
-            node.detach();
-        }
+    if options.collapse_whitespace {
+        collapse_whitespace(&mut document, options.keep_code);
     }
+
+    if let Some(base) = &base {
+        resolve_urls(&mut document, base);
+    }
+
+    report.no_text = document
+        .select(&BODY)
+        .flat_map(|body| body.text())
+        .all(|text| text.trim().is_empty());
+
+    (document, report)
 }
 
-fn is_empty_or_whitespace(el: &ElementRef) -> bool {
-    el.text().flat_map(str::chars).all(char::is_whitespace)
+/// Serialize a simplified `document` as html, or as xhtml with [SimplifyOptions::xhtml].
+fn serialize(document: &Html, options: &SimplifyOptions) -> String {
+    if options.xhtml {
+        xhtml::to_xhtml(document)
+    } else {
+        document.html()
+    }
 }
 
-fn is_image(el: &ElementRef) -> bool {
-    ["img", "picture"].contains(&el.value().name())
+/// Check that simplified `html` is well-formed, to catch tree changes that can't be represented in html.
+///
+/// The html must parse without errors, and serialize to html that doesn't change when it is parsed again.
+/// A missing doctype isn't an error, because [simplify] only keeps one from the original html.
+pub fn validate(html: &str) -> anyhow::Result<()> {
+    let has_doctype = html
+        .get(..9)
+        .map(|start| start.eq_ignore_ascii_case("<!doctype"))
+        .unwrap_or_default();
+    let html = if has_doctype {
+        Cow::Borrowed(html)
+    } else {
+        Cow::Owned(format!("<!DOCTYPE html>{html}"))
+    };
+
+    let document = Html::parse_document(&html);
+    if let Some(error) = document.errors.first() {
+        bail!("parsing simplified html: {error}");
+    }
+
+    // Parsing can add implied elements, like an empty `head`, so compare two passes instead of the original html.
+    let parsed = document.html();
+    let reparsed = Html::parse_document(&parsed).html();
+    if let Some(position) = parsed
+        .bytes()
+        .zip(reparsed.bytes())
+        .position(|(a, b)| a != b)
+        .or_else(|| (parsed.len() != reparsed.len()).then(|| parsed.len().min(reparsed.len())))
+    {
+        bail!("simplified html changes when parsed again, starting at byte {position}");
+    }
+
+    Ok(())
 }
 
-/// Remove all links, preserving any inner elements/text.
-fn remove_links(document: &mut Html) {
-    let links: Vec<_> = document
-        .select(&Selector::parse("a").unwrap())
-        .map(|el| el.id())
+/// Remove `style` from all elements, and `class` from the elements named in `strip_class`.
+///
+/// Inline styles can hide text with `display:none`, or carry large base64 background images.
+/// With `keep_ruby`, all attributes are removed from ruby elements, keeping only their structure.
+fn remove_attrs(document: &mut Html, strip_class: &[String], keep_ruby: bool) {
+    let elements: Vec<_> = document
+        .tree
+        .nodes()
+        .filter(|node| node.value().is_element())
+        .map(|node| node.id())
         .collect();
 
-    for id in links {
-        let Some(mut node) = document.tree.get_mut(id) else { continue };
-        if node.parent().is_none() {
+    for id in elements {
+        let mut node = document.tree.get_mut(id).unwrap();
+        let Node::Element(element) = node.value() else {
+            continue;
+        };
+        if keep_ruby && RUBY_ELEMENTS.contains(&element.name()) {
+            element.attrs.clear();
             continue;
         }
+        let strips_class = strip_class.iter().any(|name| name == element.name());
+        element.attrs.retain(|name, _| match name.local.as_ref() {
+            "style" => false,
+            "class" => !strips_class,
+            _ => true,
+        });
+    }
+}
 
-        // reparent to same location as node
-        while let Some(mut child) = node.first_child() {
-            let child_id = child.id();
-            child.detach();
-            node.insert_id_before(child_id);
-        }
+/// Attributes containing urls that are resolved by [resolve_urls].
+const URL_ATTRIBUTES: &[&str] = &["href", "src"];
 
-        node.detach();
+/// Make relative and protocol-relative urls absolute, so they work outside of Wikipedia.
+///
+/// Absolute urls (including `mailto:` and `tel:`) and links to fragments of the same page are not changed.
+fn resolve_urls(document: &mut Html, base: &Url) {
+    let elements: Vec<_> = document
+        .tree
+        .nodes()
+        .filter(|node| node.value().is_element())
+        .map(|node| node.id())
+        .collect();
+
+    for id in elements {
+        let mut node = document.tree.get_mut(id).unwrap();
+        let Node::Element(element) = node.value() else {
+            continue;
+        };
+        for (name, value) in element.attrs.iter_mut() {
+            if !URL_ATTRIBUTES.contains(&name.local.as_ref()) {
+                continue;
+            }
+            if value.starts_with('#') || Url::parse(value).is_ok() {
+                continue;
+            }
+            match base.join(value) {
+                Ok(url) => *value = url.as_str().into(),
+                Err(e) => debug!("Unable to resolve url {:?}: {}", value.as_ref(), e),
+            }
+        }
+    }
+}
+
+/// Select the elements matching `selector` that are still in the document.
+///
+/// Unlike [Html::select], this skips elements in subtrees that have been detached.
+fn select_attached<'a>(
+    document: &'a Html,
+    selector: &'a Selector,
+) -> impl Iterator<Item = ElementRef<'a>> + 'a {
+    document
+        .tree
+        .root()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|el| selector.matches(el))
+}
+
+/// Get the trimmed `alt` text of an `img`, if it is descriptive.
+///
+/// The fallback images of math formulas are skipped, their alt text is the LaTeX source, see [SimplifyOptions::math_mode].
+fn image_alt<'a>(el: &ElementRef<'a>) -> Option<&'a str> {
+    let img = el.value();
+    if img.name() != "img"
+        || img
+            .classes()
+            .any(|c| c.starts_with("mwe-math-fallback-image"))
+    {
+        return None;
+    }
+    let alt = img.attr("alt")?.trim();
+    (!alt.is_empty()).then_some(alt)
+}
+
+fn remove_ids(document: &mut Html, ids: impl IntoIterator<Item = NodeId>) {
+    for id in ids {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+/// If `el` is a `sup` referencing a citation or note, like `[1]` or a link to `#cite_note-1`.
+///
+/// Superscripts with other content, like the exponent in `x<sup>2</sup>`, aren't citations.
+fn is_citation(el: &ElementRef) -> bool {
+    if el.value().name() != "sup" {
+        return false;
+    }
+
+    let text: String = el.text().collect();
+    let text = text.trim();
+    if text.len() > 2 && text.starts_with('[') && text.ends_with(']') {
+        return true;
+    }
+
+    let mut links = el
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| e.value().name() == "a")
+        .peekable();
+    links.peek().is_some()
+        && links.all(|a| {
+            a.value()
+                .attr("href")
+                .map(|href| href.contains("#cite_note"))
+                .unwrap_or_default()
+        })
+}
+
+/// Elements that are hidden by their class, and the content of collapsed blocks.
+///
+/// The headers of collapsible blocks, like `.mw-collapsible` tables or navboxes, aren't hidden.
+static HIDDEN: Lazy<Selector> =
+    Lazy::new(|| Selector::parse(".hidden, .mw-collapsed > .mw-collapsible-content").unwrap());
+
+/// If `el` is hidden from readers, with `display:none` or `visibility:hidden` in its inline style, or see [HIDDEN].
+///
+/// These are removed before the `style` attributes, so stripping them doesn't reveal the text.
+/// MathML formulas are hidden in favor of a fallback image, so they are kept for [SimplifyOptions::math_mode].
+fn is_hidden(el: &ElementRef) -> bool {
+    if HIDDEN.matches(el) {
+        return true;
+    }
+
+    let Some(style) = el.value().attr("style") else {
+        return false;
+    };
+    if el.select(&MATH).next().is_some() {
+        return false;
+    }
+    style.split(';').any(|declaration| {
+        let declaration: String = declaration
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        declaration.starts_with("display:none") || declaration.starts_with("visibility:hidden")
+    })
+}
+
+fn is_empty_or_whitespace(el: &ElementRef) -> bool {
+    el.text().flat_map(str::chars).all(char::is_whitespace)
+}
+
+/// Remove `chars` from all text nodes, and any text nodes left empty.
+fn strip_text(document: &mut Html, chars: &[char]) {
+    if chars.is_empty() {
+        return;
+    }
+
+    let changes: Vec<_> = document
+        .tree
+        .root()
+        .descendants()
+        .filter_map(|node| match node.value() {
+            Node::Text(text) if text.contains(chars) => Some((node.id(), text.replace(chars, ""))),
+            _ => None,
+        })
+        .collect();
+
+    for (id, stripped) in changes {
+        let mut node = document.tree.get_mut(id).unwrap();
+        if stripped.is_empty() {
+            node.detach();
+        } else if let Node::Text(text) = node.value() {
+            text.text = stripped.into();
+        }
+    }
+}
+
+/// Elements whose whitespace is significant.
+const PREFORMATTED_ELEMENTS: &[&str] = &["pre", "textarea"];
+
+/// Elements containing code, kept intact with [SimplifyOptions::keep_code].
+const CODE_ELEMENTS: &[&str] = &["code", "pre"];
+
+/// If `node` is, or is inside of, a code element.
+fn is_code(node: ego_tree::NodeRef<Node>) -> bool {
+    std::iter::once(node).chain(node.ancestors()).any(|n| {
+        n.value()
+            .as_element()
+            .map(|el| CODE_ELEMENTS.contains(&el.name()))
+            .unwrap_or_default()
+    })
+}
+
+/// Elements that are laid out on their own lines, so whitespace next to them isn't displayed.
+///
+/// Everything else is treated as inline, so unknown elements can't merge the words around them.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Collapse whitespace in all text nodes outside of preformatted elements, and outside of `code` if `keep_code` is set.
+///
+/// Whitespace at the start or end of a block element, or next to one, is removed entirely.
+/// Text nodes left empty are removed.
+fn collapse_whitespace(document: &mut Html, keep_code: bool) {
+    let is_block = |node: ego_tree::NodeRef<Node>| {
+        node.value()
+            .as_element()
+            .map(|el| BLOCK_ELEMENTS.contains(&el.name()))
+            .unwrap_or_default()
+    };
+
+    let mut changes = Vec::new();
+    for node in document.tree.root().descendants() {
+        let Node::Text(text) = node.value() else {
+            continue;
+        };
+        let is_preformatted = node.ancestors().any(|a| {
+            a.value()
+                .as_element()
+                .map(|el| PREFORMATTED_ELEMENTS.contains(&el.name()))
+                .unwrap_or_default()
+        });
+        if is_preformatted || (keep_code && is_code(node)) {
+            continue;
+        }
+
+        let starts_block = match node.prev_sibling() {
+            Some(prev) => is_block(prev),
+            None => node.parent().map(is_block).unwrap_or_default(),
+        };
+        let ends_block = match node.next_sibling() {
+            Some(next) => is_block(next),
+            None => node.parent().map(is_block).unwrap_or_default(),
+        };
+
+        let mut collapsed = collapse_spaces(text);
+        if starts_block {
+            collapsed = collapsed.trim_start_matches(' ').to_owned();
+        }
+        if ends_block {
+            collapsed = collapsed.trim_end_matches(' ').to_owned();
+        }
+        if collapsed != text.as_ref() {
+            changes.push((node.id(), collapsed));
+        }
+    }
+
+    for (id, collapsed) in changes {
+        let mut node = document.tree.get_mut(id).unwrap();
+        if collapsed.is_empty() {
+            node.detach();
+        } else if let Node::Text(text) = node.value() {
+            text.text = collapsed.into();
+        }
+    }
+}
+
+/// Replace each run of whitespace in `text` with a single space.
+///
+/// No-break spaces are kept, as they change how the text is wrapped.
+fn collapse_spaces(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() && !matches!(c, '\u{00A0}' | '\u{2007}' | '\u{202F}') {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+static PARAGRAPH: Lazy<Selector> = Lazy::new(|| Selector::parse("p").unwrap());
+static BODY: Lazy<Selector> = Lazy::new(|| Selector::parse("body").unwrap());
+
+/// Images that are kept even though they have no text, if allowed by the [ElementFilter].
+static MEDIA: Lazy<Selector> = Lazy::new(|| Selector::parse("img, picture").unwrap());
+
+fn contains_media(el: &ElementRef) -> bool {
+    MEDIA.matches(el) || el.select(&MEDIA).next().is_some()
+}
+
+/// Like [contains_media], but only for images that aren't removed by `filter`.
+fn contains_kept_media(el: &ElementRef, filter: &ElementFilter) -> bool {
+    iter::once(*el)
+        .filter(|el| MEDIA.matches(el))
+        .chain(el.select(&MEDIA))
+        .any(|media| !filter.should_remove(&media))
+}
+
+/// Remove paragraphs without any letters or digits, e.g. the `.` left after removing a trailing reference.
+///
+/// Returns the number of paragraphs removed.
+fn remove_empty_paragraphs(document: &mut Html) -> usize {
+    let paragraphs: Vec<_> = select_attached(document, &PARAGRAPH)
+        .filter(|p| !p.text().flat_map(str::chars).any(char::is_alphanumeric) && !contains_media(p))
+        .map(|p| p.id())
+        .collect();
+
+    let removed = paragraphs.len();
+    remove_ids(document, paragraphs);
+    removed
+}
+
+static LIST_ITEM: Lazy<Selector> = Lazy::new(|| Selector::parse("li").unwrap());
+static LIST: Lazy<Selector> = Lazy::new(|| Selector::parse("ul, ol").unwrap());
+
+/// Remove list items without any letters or digits, like those that only had a reference,
+/// and then the lists left without any items.
+///
+/// Nested lists are kept in the items that contain them.
+/// Returns the number of items and lists removed.
+fn remove_empty_lists(document: &mut Html) -> usize {
+    let items: Vec<_> = select_attached(document, &LIST_ITEM)
+        .filter(|li| {
+            !li.text().flat_map(str::chars).any(char::is_alphanumeric) && !contains_media(li)
+        })
+        .map(|li| li.id())
+        .collect();
+    let mut removed = items.len();
+    remove_ids(document, items);
+
+    let lists: Vec<_> = select_attached(document, &LIST)
+        .filter(|list| {
+            !list
+                .children()
+                .filter_map(ElementRef::wrap)
+                .any(|child| child.value().name() == "li")
+        })
+        .map(|list| list.id())
+        .collect();
+    removed += lists.len();
+    remove_ids(document, lists);
+
+    removed
+}
+
+/// Remove all links, preserving any inner elements/text.
+///
+/// Returns the number of links removed.
+fn remove_links(document: &mut Html) -> usize {
+    let mut removed = 0;
+    let links: Vec<_> = document
+        .select(&Selector::parse("a").unwrap())
+        .map(|el| el.id())
+        .collect();
+
+    let root = document.tree.root().id();
+    for id in links {
+        // `select` also matches elements within detached subtrees, skip those.
+        let Some(node) = document.tree.get(id) else {
+            continue;
+        };
+        if !node.ancestors().any(|ancestor| ancestor.id() == root) {
+            continue;
+        }
+        replace_with_children(document, id);
+        removed += 1;
     }
+
+    removed
+}
+
+/// Replace the node `id` with its children, in the same location.
+fn replace_with_children(document: &mut Html, id: NodeId) {
+    let mut node = document.tree.get_mut(id).unwrap();
+
+    // reparent to same location as node
+    while let Some(mut child) = node.first_child() {
+        let child_id = child.id();
+        child.detach();
+        node.insert_id_before(child_id);
+    }
+
+    node.detach();
 }
 
 #[cfg(test)]
@@ -122,6 +1559,1072 @@ mod test {
         assert!(!CONFIG.sections_to_remove.is_empty());
     }
 
+    #[test]
+    fn static_config_is_nfc() {
+        for (lang, titles) in &CONFIG.sections_to_remove {
+            for title in titles {
+                assert!(
+                    is_nfc(title),
+                    "{title:?} for {lang:?} is not NFC-normalized"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn section_counts() {
+        let counts = super::section_counts(&Default::default());
+        for lang in ["en", "de", "fr", "es", "ru"] {
+            assert!(counts[lang] > 0, "{lang} should have sections: {counts:?}");
+        }
+        assert_eq!(None, counts.get("eo"));
+
+        let options = SimplifyOptions {
+            sections_to_remove: [
+                ("eo".to_owned(), ["Referencoj".to_owned()].into()),
+                (
+                    "en".to_owned(),
+                    ["See also".to_owned(), "Gallery".to_owned()].into(),
+                ),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let custom = super::section_counts(&options);
+        assert_eq!(1, custom["eo"]);
+        // "See also" is already built in.
+        assert_eq!(counts["en"] + 1, custom["en"]);
+
+        let options = SimplifyOptions {
+            builtin_sections: false,
+            ..options
+        };
+        assert_eq!(
+            BTreeMap::from([("en".to_owned(), 2), ("eo".to_owned(), 1)]),
+            super::section_counts(&options)
+        );
+    }
+
+    #[test]
+    fn load_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        // "ŭ" as "u" and a combining breve.
+        fs::write(
+            &path,
+            "{\"sections_to_remove\": {\"eo\": [\"Vidu anka\u{0075}\u{0306}\"], \"en\": [\"Gallery\"]}}",
+        )
+        .unwrap();
+
+        let options = SimplifyOptions {
+            sections_to_remove: super::load_config_file(&path).unwrap(),
+            builtin_sections: false,
+            ..Default::default()
+        };
+        let html = r#"<html><head></head><body>
+            <h2>Historio</h2><p>Kept.</p>
+            <h2>Vidu ankaŭ</h2><p>Removed.</p>
+        </body></html>"#;
+        let output = simplify_with(html, "eo", &options);
+        assert!(output.contains("Kept."));
+        assert!(!output.contains("Removed."));
+
+        // The built-in sections aren't removed.
+        let html = r#"<html><head></head><body>
+            <h2>See also</h2><p>Kept.</p>
+            <h2>Gallery</h2><p>Removed.</p>
+        </body></html>"#;
+        let output = simplify_with(html, "en", &options);
+        assert!(output.contains("Kept."));
+        assert!(!output.contains("Removed."));
+    }
+
+    #[test]
+    fn load_sections_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sections.json");
+        fs::write(
+            &path,
+            r#"{"sections_to_remove": {"eo": ["Vidu ankaŭ", "Referencoj"]}}"#,
+        )
+        .unwrap();
+
+        let options = SimplifyOptions {
+            sections_to_remove: super::load_sections_file(&path).unwrap(),
+            ..Default::default()
+        };
+        let html = r#"<html><head></head><body>
+            <h2>Historio</h2><p>Kept.</p>
+            <h2>Vidu ankaŭ</h2><p>Removed.</p>
+            <h2>Referencoj</h2><p>Removed.</p>
+        </body></html>"#;
+        let (output, report) = simplify_with_report(html, "eo", &options);
+        assert_eq!(2, report.sections_removed);
+        assert!(output.contains("Kept."));
+        assert!(!output.contains("Removed."));
+
+        // "ŭ" as "u" and a combining breve.
+        fs::write(
+            &path,
+            "{\"sections_to_remove\": {\"eo\": [\"Vidu anka\u{0075}\u{0306}\"]}}",
+        )
+        .unwrap();
+        assert!(super::load_sections_file(&path).is_err());
+    }
+
+    #[test]
+    fn detect_lang() {
+        let html =
+            r#"<html><head><base href="//de.wikipedia.org/wiki/"/></head><body></body></html>"#;
+        assert_eq!(Some("de".to_string()), super::detect_lang(html));
+
+        let html = r#"<html><head></head><body><p>No base</p></body></html>"#;
+        assert_eq!(None, super::detect_lang(html));
+
+        let html = r#"<html lang="de"><head></head><body><p>No base</p></body></html>"#;
+        assert_eq!(Some("de".to_string()), super::detect_lang(html));
+
+        let html = r#"<html lang="zh-Hans-CN"><head></head><body></body></html>"#;
+        assert_eq!(Some("zh".to_string()), super::detect_lang(html));
+
+        let html =
+            r#"<html><head><base href="//zh-yue.wikipedia.org/wiki/"/></head><body></body></html>"#;
+        assert_eq!(Some("zh-yue".to_string()), super::detect_lang(html));
+
+        let html = r#"<html lang="en"><head><base href="//fr.wikipedia.org/wiki/"/></head><body></body></html>"#;
+        assert_eq!(Some("fr".to_string()), super::detect_lang(html));
+
+        let html = r#"<html><head><meta http-equiv="Content-Language" content="es"></head><body></body></html>"#;
+        assert_eq!(Some("es".to_string()), super::detect_lang(html));
+    }
+
+    #[test]
+    fn extract_toc() {
+        let document = Html::parse_document(
+            r#"<html><body>
+            <section data-mw-section-id="0"><p>Lead.</p></section>
+            <section data-mw-section-id="1"><h2 id="History">History</h2>
+                <section data-mw-section-id="2"><h3 id="Early_years">Early   <i>years</i></h3></section>
+                <section data-mw-section-id="3"><h3 id="Today">Today</h3>
+                    <section data-mw-section-id="4"><h4 id="Notes">Notes</h4></section>
+                </section>
+            </section>
+            <section data-mw-section-id="5"><h2 id="Geography">Geography</h2>
+                <section data-mw-section-id="6"><h3 id="Notes_2">Notes</h3></section>
+            </section>
+            <section data-mw-section-id="7"><h2>References</h2></section>
+            </body></html>"#,
+        );
+
+        let entry = |level, text: &str, id: Option<&str>| TocEntry {
+            level,
+            text: text.to_owned(),
+            id: id.map(str::to_owned),
+        };
+        assert_eq!(
+            vec![
+                entry(2, "History", Some("History")),
+                entry(3, "Early years", Some("Early_years")),
+                entry(3, "Today", Some("Today")),
+                entry(4, "Notes", Some("Notes")),
+                entry(2, "Geography", Some("Geography")),
+                entry(3, "Notes", Some("Notes_2")),
+                entry(2, "References", None),
+            ],
+            super::extract_toc(&document)
+        );
+
+        let document = Html::parse_document("<html><body><p>No sections.</p></body></html>");
+        assert!(super::extract_toc(&document).is_empty());
+    }
+
+    #[test]
+    fn extract_thumbnail() {
+        let document = Html::parse_document(
+            r#"<html><head><base href="//en.wikipedia.org/wiki/"></head><body>
+            <section data-mw-section-id="0">
+                <table class="infobox"><tbody>
+                    <tr><td><span class="flagicon"><img src="//upload.wikimedia.org/Flag.svg.png" width="23" height="15"></span> Country</td></tr>
+                    <tr><td><img src="//upload.wikimedia.org/Logo.svg.png" width="20" height="20"></td></tr>
+                    <tr><td><span typeof="mw:File"><img src="//upload.wikimedia.org/Skyline.jpg" width="250" height="167"></span></td></tr>
+                </tbody></table>
+                <p>Lead.</p>
+            </section>
+            </body></html>"#,
+        );
+        assert_eq!(
+            Some("https://upload.wikimedia.org/Skyline.jpg".to_owned()),
+            super::extract_thumbnail(&document)
+        );
+
+        let document = Html::parse_document(
+            r#"<html><head><base href="//en.wikipedia.org/wiki/"></head><body>
+            <section data-mw-section-id="0">
+                <p><span class="noviewer"><img src="//upload.wikimedia.org/Icon.png" width="100" height="100"></span> Lead.</p>
+            </section>
+            <section data-mw-section-id="1"><h2>History</h2>
+                <img src="//upload.wikimedia.org/Old_photo.jpg" width="250" height="167">
+            </section>
+            </body></html>"#,
+        );
+        assert_eq!(None, super::extract_thumbnail(&document));
+    }
+
+    #[test]
+    fn extract_article() {
+        let html = r#"<html lang="en"><head><base href="//de.wikipedia.org/wiki/"></head><body>
+            <section data-mw-section-id="0">
+                <p><span class="geo-inline"><span class="geo">52.516667; 13.383333</span></span></p>
+                <table class="infobox"><tbody><tr><td>
+                    <img src="//upload.wikimedia.org/Brandenburger_Tor.jpg" width="250" height="167">
+                </td></tr></tbody></table>
+                <p>Das <a href="./Tor">Tor</a> steht in Berlin.</p>
+            </section>
+            <section data-mw-section-id="1"><h2>Literatur</h2><p>Removed.</p></section>
+        </body></html>"#;
+
+        let article = super::extract_article(html, None, &Default::default()).unwrap();
+        assert_eq!("de", article.lang);
+        assert_eq!(None, article.is_redirect);
+        assert_eq!(Some((52.516667, 13.383333)), article.coordinates);
+        assert_eq!(
+            Some("https://upload.wikimedia.org/Brandenburger_Tor.jpg".to_owned()),
+            article.thumbnail
+        );
+        assert!(article.simplified_html.contains("Das Tor steht in Berlin."));
+        assert!(!article.simplified_html.contains("Removed."));
+        assert_eq!(PageKind::Stub, article.kind);
+        assert_eq!(
+            "52.516667; 13.383333Das Tor steht in Berlin."
+                .chars()
+                .count(),
+            article.text_len
+        );
+
+        assert_eq!(
+            "en",
+            super::extract_article(html, Some("en"), &Default::default())
+                .unwrap()
+                .lang
+        );
+
+        let options = SimplifyOptions {
+            builtin_sections: false,
+            ..Default::default()
+        };
+        let article = super::extract_article(html, None, &options).unwrap();
+        assert!(article.simplified_html.contains("Removed."));
+
+        let redirect = r#"<html><head></head><body>
+            <link rel="mw:PageProp/redirect" href="./K%C3%B6ln_Hauptbahnhof">
+        </body></html>"#;
+        assert!(super::extract_article(redirect, None, &Default::default()).is_err());
+        let article = super::extract_article(redirect, Some("de"), &Default::default()).unwrap();
+        assert_eq!(Some("Köln Hauptbahnhof".to_owned()), article.is_redirect);
+        assert_eq!(None, article.coordinates);
+        assert_eq!(None, article.thumbnail);
+        assert_eq!(PageKind::Redirect, article.kind);
+    }
+
+    #[test]
+    fn classify() {
+        let classify = |html: &str| super::classify(&Html::parse_document(html));
+        let text =
+            "Berlin is the capital and largest city of Germany, both by area and by population. "
+                .repeat(5);
+
+        let article = format!("<html><head></head><body><p>{text}</p></body></html>");
+        assert_eq!(PageKind::Article, classify(&article));
+
+        let short = "<html><head></head><body><p>Berlin is a city.</p></body></html>";
+        assert_eq!(PageKind::Stub, classify(short));
+
+        let marked = format!(
+            r#"<html><head></head><body><p>{text}</p>
+            <div role="note" class="metadata plainlinks asbox stub"><p>This German location article is a stub.</p></div>
+            </body></html>"#
+        );
+        assert_eq!(PageKind::Stub, classify(&marked));
+
+        let disambiguation = r#"<html><head><meta property="mw:PageProp/disambiguation"/></head><body>
+            <p><b>Mercury</b> may refer to:</p>
+        </body></html>"#;
+        assert_eq!(PageKind::Disambiguation, classify(disambiguation));
+
+        let redirect = r#"<html><head></head><body>
+            <link rel="mw:PageProp/redirect" href="./K%C3%B6ln_Hauptbahnhof">
+        </body></html>"#;
+        assert_eq!(PageKind::Redirect, classify(redirect));
+        assert_eq!("redirect", PageKind::Redirect.to_string());
+    }
+
+    #[test]
+    fn is_redirect() {
+        let is_redirect = |html: &str| super::is_redirect(&Html::parse_document(html));
+        let redirect = r#"<html><head></head><body>
+            <link rel="mw:PageProp/redirect" href="./K%C3%B6ln_Hauptbahnhof">
+        </body></html>"#;
+        assert!(is_redirect(redirect));
+
+        let article = r#"<html><head><link rel="mw:PageProp/Category" href="./Kategorie:Bahnhof"></head><body>
+            <p>Der <a rel="mw:WikiLink" href="./K%C3%B6ln_Hauptbahnhof">Kölner Hauptbahnhof</a> ist ein Bahnhof.</p>
+        </body></html>"#;
+        assert!(!is_redirect(article));
+    }
+
+    #[test]
+    fn is_disambiguation() {
+        let is_disambiguation = |html: &str| super::is_disambiguation(&Html::parse_document(html));
+        let disambiguation = r#"<html><head><meta property="mw:PageProp/disambiguation"/></head><body>
+            <p><b>Mercury</b> may refer to:</p>
+            <ul><li><a href="./Mercury_(planet)">Mercury (planet)</a></li><li><a href="./Mercury_(element)">Mercury (element)</a></li></ul>
+            <table id="disambigbox" class="metadata plainlinks dmbox dmbox-disambig" role="presentation"><tbody><tr><td>This disambiguation page lists articles associated with the title Mercury.</td></tr></tbody></table>
+        </body></html>"#;
+        assert!(is_disambiguation(disambiguation));
+
+        let article = r#"<html><head></head><body>
+            <div role="note" class="hatnote">For other uses, see <a href="./Mercury_(disambiguation)" class="mw-disambig">Mercury (disambiguation)</a>.</div>
+            <p><b>Mercury</b> is the first planet from the Sun.</p>
+        </body></html>"#;
+        assert!(!is_disambiguation(article));
+    }
+
+    #[test]
+    fn element_filter() {
+        let html = r#"<html><body>
+            <p>Body text</p>
+            <div class="infobox"><p>Infobox</p></div>
+            <div class="infobox keep"><p>Kept infobox</p></div>
+        </body></html>"#;
+
+        let default = simplify(html, "en");
+        assert!(default.contains("Infobox"));
+
+        let options = SimplifyOptions {
+            elements: ElementFilter::new([".infobox"], [".keep"]).unwrap(),
+            ..Default::default()
+        };
+        let custom = simplify_with(html, "en", &options);
+        assert!(custom.contains("Body text"));
+        assert!(!custom.contains(">Infobox<"));
+        assert!(custom.contains("Kept infobox"));
+
+        let e = ElementFilter::new(["p", "div >"], None::<&str>).unwrap_err();
+        assert!(
+            e.to_string().contains("\"div >\""),
+            "Error should name the invalid selector: {e}"
+        );
+    }
+
+    #[test]
+    fn remove_navigation() {
+        // Excerpt of the "Brandenburg Gate" article structure from the Enterprise dump.
+        let html = r#"<html><head></head><body>
+        <section data-mw-section-id="0">
+            <div role="note" class="hatnote navigation-not-searchable">For the U-Bahn station, see <a href="./Brandenburger_Tor_station">Brandenburger Tor station</a>.</div>
+            <p>The <b>Brandenburg Gate</b> is an 18th-century neoclassical monument in Berlin.</p>
+            <p class="mw-empty-elt"> </p>
+        </section>
+        <section data-mw-section-id="1">
+            <h2 id="History">History</h2>
+            <div class="hatnote">Main article: <a href="./History_of_Berlin">History of Berlin</a></div>
+            <p>The gate was commissioned by King Frederick William II.</p>
+            <table class="vertical-navbox nowraplinks"><tbody><tr><td>Part of a series on Berlin</td></tr></tbody></table>
+        </section>
+        <section data-mw-section-id="2">
+            <div role="navigation" class="navbox"><table class="nowraplinks"><tbody><tr><th>Landmarks of Berlin</th></tr></tbody></table></div>
+            <div class="navigation-box">Berlin portal</div>
+        </section>
+        </body></html>"#;
+
+        let simplified = simplify(html, "en");
+
+        for removed in [
+            "U-Bahn station",
+            "Main article",
+            "Part of a series",
+            "Landmarks of Berlin",
+            "Berlin portal",
+            "mw-empty-elt",
+        ] {
+            assert!(
+                !simplified.contains(removed),
+                "{removed:?} should be removed: {simplified}"
+            );
+        }
+        assert!(simplified.contains("is an 18th-century neoclassical monument in Berlin."));
+        assert!(simplified.contains("The gate was commissioned by King Frederick William II."));
+    }
+
+    #[test]
+    fn keep_definition_lists() {
+        // Excerpt of the "Glossary of cricket terms" article structure from the Enterprise dump.
+        let html = r#"<html><head></head><body>
+        <section data-mw-section-id="1">
+            <h2 id="A">A</h2>
+            <dl class="glossary">
+                <dt class="glossary" id="all-rounder"><dfn class="glossary">all-rounder</dfn></dt>
+                <dd class="glossary">A player who is adept at both batting and bowling.</dd>
+                <dt class="glossary" id="appeal"><dfn class="glossary">appeal</dfn></dt>
+                <dd class="glossary">The act of a fielder asking an umpire whether a batter is out.</dd>
+            </dl>
+            <blockquote><p>Cricket is a game which the British, not being a spiritual people, had to invent.</p></blockquote>
+        </section>
+        </body></html>"#;
+        let expected_text = [
+            "all-rounder",
+            "A player who is adept at both batting and bowling.",
+            "appeal",
+            "The act of a fielder asking an umpire whether a batter is out.",
+            "Cricket is a game which the British, not being a spiritual people, had to invent.",
+        ];
+        let text = |html: &str| -> Vec<String> {
+            Html::parse_document(html)
+                .root_element()
+                .text()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+
+        let mut expected = vec!["A"];
+        expected.extend(expected_text);
+        assert_eq!(text(&simplify(html, "en")), expected);
+
+        // Kept even if a broader deny list would match them.
+        let mut options = SimplifyOptions {
+            elements: ElementFilter::new([".glossary", "blockquote"], None::<&str>).unwrap(),
+            ..Default::default()
+        };
+        let simplified = simplify_with(html, "en", &options);
+        for t in expected_text {
+            assert!(simplified.contains(t), "{t:?} should be kept: {simplified}");
+        }
+
+        options.keep_definition_lists = false;
+        let simplified = simplify_with(html, "en", &options);
+        assert!(!simplified.contains("all-rounder"));
+        assert!(!simplified.contains("Cricket"));
+    }
+
+    #[test]
+    fn math_mode() {
+        // Inline formula structure from the Enterprise dump.
+        let html = r#"<html><head></head><body>
+            <p>The area is <span class="mwe-math-element"><span class="mwe-math-mathml-inline mwe-math-mathml-a11y" style="display: none;"><math xmlns="http://www.w3.org/1998/Math/MathML" alttext="{\displaystyle \pi r^{2}}"><semantics><mrow><mi>π</mi><msup><mi>r</mi><mn>2</mn></msup></mrow></semantics></math></span><img src="//wikimedia.org/api/rest_v1/media/math/render/svg/abc" class="mwe-math-fallback-image-inline" alt="{\displaystyle \pi r^{2}}"></span> for radius r.</p>
+        </body></html>"#;
+        let with_mode = |math_mode| {
+            simplify_with(
+                html,
+                "en",
+                &SimplifyOptions {
+                    math_mode,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let keep = with_mode(MathMode::Keep);
+        assert!(keep.contains("<mi>π</mi>"));
+        assert!(keep.contains(r#"alttext="{\displaystyle \pi r^{2}}""#));
+
+        let alttext = with_mode(MathMode::AltText);
+        assert!(!alttext.contains("<math"));
+        assert!(
+            alttext.contains(r#"The area is \pi r^{2} for radius r."#),
+            "{alttext}"
+        );
+
+        let drop = with_mode(MathMode::Drop);
+        assert!(!drop.contains("<math"));
+        assert!(!drop.contains("mwe-math-element"));
+        assert!(drop.contains("The area is  for radius r."), "{drop}");
+    }
+
+    #[test]
+    fn table_mode() {
+        let html = r#"<html><head></head><body>
+            <p>Largest cities.</p>
+            <table class="wikitable">
+                <caption>Population</caption>
+                <tbody>
+                    <tr><th>City</th><th colspan="2">Population</th></tr>
+                    <tr><td rowspan="2"><a href="./Berlin">Berlin</a></td><td>2020</td><td>3,664,088</td></tr>
+                    <tr><td>2010</td><td>3,460,725</td></tr>
+                    <tr><td>Ham<b>burg</b><sup class="reference"><a href="./Cities#cite_note-1">[1]</a></sup></td><td>2020</td><td>1,852,478</td></tr>
+                </tbody>
+            </table>
+        </body></html>"#;
+        let with_mode = |table_mode| {
+            simplify_with(
+                html,
+                "en",
+                &SimplifyOptions {
+                    table_mode,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let keep = with_mode(TableMode::KeepHtml);
+        assert!(keep.contains("<table"));
+
+        let text = with_mode(TableMode::Text);
+        assert!(!text.contains("<table"), "{text}");
+        let pre: Vec<_> = Html::parse_document(&text)
+            .select(&Selector::parse("pre").unwrap())
+            .map(|pre| pre.text().collect::<String>())
+            .collect();
+        assert_eq!(
+            vec![[
+                "Population",
+                "City\tPopulation\t",
+                "----\t----------\t---",
+                "Berlin\t2020\t3,664,088",
+                "Berlin\t2010\t3,460,725",
+                "Hamburg\t2020\t1,852,478",
+            ]
+            .join("\n")],
+            pre
+        );
+        assert!(text.contains("Largest cities."));
+
+        let drop = with_mode(TableMode::Drop);
+        assert!(!drop.contains("<table"));
+        assert!(!drop.contains("Population"));
+        assert!(drop.contains("Largest cities."));
+    }
+
+    #[test]
+    fn ruby_mode() {
+        let html = r#"<html><head></head><body>
+            <p><ruby class="furigana" lang="ja">東<rp>(</rp><rt style="font-size:50%">とう</rt><rp>)</rp>京<rp>(</rp><rt>きょう</rt><rp>)</rp></ruby>は日本の首都です。</p>
+        </body></html>"#;
+        let with_mode = |ruby_mode| {
+            let output = simplify_with(
+                html,
+                "ja",
+                &SimplifyOptions {
+                    ruby_mode,
+                    ..Default::default()
+                },
+            );
+            Html::parse_document(&output)
+                .select(&PARAGRAPH)
+                .next()
+                .unwrap()
+                .inner_html()
+        };
+
+        let kept = "<ruby>東<rp>(</rp><rt>とう</rt><rp>)</rp>京<rp>(</rp><rt>きょう</rt><rp>)</rp></ruby>は日本の首都です。";
+        assert_eq!(kept, with_mode(RubyMode::Auto));
+        assert_eq!(kept, with_mode(RubyMode::Keep));
+        assert_eq!("東京は日本の首都です。", with_mode(RubyMode::Flatten));
+
+        assert_eq!(RubyMode::Keep, RubyMode::Auto.for_lang("zh-yue"));
+        assert_eq!(RubyMode::Auto, RubyMode::Auto.for_lang("en"));
+        assert_eq!(RubyMode::Flatten, RubyMode::Flatten.for_lang("ja"));
+    }
+
+    #[test]
+    fn validate() {
+        let html = r#"<html><head><title>Berlin</title></head><body>
+            <section><h2>Geschichte</h2><p>Berlin ist die <b>Hauptstadt</b> Deutschlands.<br>Und ein Land.</p></section>
+            <ul><li>Eins</li><li>Zwei &amp; drei</li></ul>
+        </body></html>"#;
+        let doctype = format!("<!DOCTYPE html>{html}");
+        // Without a title the empty `head` is removed, and added back when parsed.
+        let no_head = html.replace("<title>Berlin</title>", "");
+        for html in [html, &doctype, &no_head] {
+            let output = simplify(html, "de");
+            if let Err(e) = super::validate(&output) {
+                panic!("{output}: {e:#}");
+            }
+        }
+
+        // A paragraph can't contain a block element, so it is closed when parsed again.
+        let mut document = Html::parse_document(html);
+        let p = document.select(&PARAGRAPH).next().unwrap().id();
+        let div = Element::new(
+            QualName::new(None, ns!(html), local_name!("div")),
+            Vec::new(),
+        );
+        document.tree.get_mut(p).unwrap().append(Node::Element(div));
+        assert!(super::validate(&document.html()).is_err());
+
+        assert!(
+            super::validate("<html><head></head><body><p>Unclosed</b></p></body></html>").is_err()
+        );
+    }
+
+    #[test]
+    fn remove_empty_paragraphs() {
+        let html = r#"<html><head></head><body>
+            <p>Some text.<sup class="reference"><a href="./Article#cite_note-1">[1]</a></sup></p>
+            <p><sup class="reference"><a href="./Article#cite_note-2">[2]</a></sup>.</p>
+            <p><sup class="reference"><a href="./Article#cite_note-3">[3]</a></sup> ; </p>
+            <p>1990</p>
+            <p><img src="image.png" alt="Image"></p>
+        </body></html>"#;
+        let options = SimplifyOptions {
+            elements: ElementFilter::new(["sup.reference"], ["img"]).unwrap(),
+            ..Default::default()
+        };
+
+        let (output, report) = simplify_with_report(html, "en", &options);
+        let document = Html::parse_document(&output);
+        let paragraphs: Vec<String> = document
+            .select(&PARAGRAPH)
+            .map(|p| p.inner_html())
+            .collect();
+
+        assert_eq!(2, report.empty_paragraphs_removed);
+        assert_eq!(vec!["Some text.", "1990"], paragraphs[..2]);
+        assert!(paragraphs[2].starts_with("<img"), "{paragraphs:?}");
+        assert_eq!(3, paragraphs.len());
+
+        // Images are removed by default, and so are the elements left empty without them.
+        let html = r#"<html><head></head><body>
+            <p>Some text.</p>
+            <figure typeof="mw:File/Thumb"><a href="./File:Image.png"><img src="image.png"></a><figcaption></figcaption></figure>
+        </body></html>"#;
+        let output = simplify(html, "en");
+        assert!(!output.contains("figure"), "{output}");
+    }
+
+    #[test]
+    fn remove_empty_lists() {
+        let html = r#"<html><head></head><body>
+            <ul>
+                <li>First<sup class="reference"><a href="./Article#cite_note-1">[1]</a></sup></li>
+                <li><sup class="reference"><a href="./Article#cite_note-2">[2]</a></sup></li>
+                <li>Third
+                    <ol><li>Nested</li><li> </li></ol>
+                </li>
+            </ul>
+            <ol><li><sup class="reference"><a href="./Article#cite_note-3">[3]</a></sup>.</li></ol>
+        </body></html>"#;
+
+        let (output, report) = simplify_with_report(html, "en", &Default::default());
+        let document = Html::parse_document(&output);
+        let items: Vec<String> = document
+            .select(&LIST_ITEM)
+            .map(|li| li.text().collect::<String>().trim().to_owned())
+            .collect();
+
+        assert_eq!(vec!["First", "ThirdNested", "Nested"], items);
+        assert_eq!(
+            1,
+            document
+                .select(&Selector::parse("ul > li > ol").unwrap())
+                .count()
+        );
+        assert_eq!(2, document.select(&LIST).count());
+        // The reference-only items and the `ol` they were in.
+        assert_eq!(3, report.empty_lists_removed);
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        let html = "<html><head></head><body>\n  <p>  foo\n\n   bar </p>\n  \
+            <p>Some <b>bold</b>\t and\u{00A0}\u{00A0}more.</p>\n  \
+            <pre>  keep\n\n   this </pre>\n</body></html>";
+        let paragraphs = |output: &str| -> Vec<String> {
+            Html::parse_document(output)
+                .select(&Selector::parse("p, pre").unwrap())
+                .map(|el| el.inner_html())
+                .collect()
+        };
+
+        let output = simplify(html, "en");
+        assert_eq!(
+            vec![
+                "foo bar",
+                "Some <b>bold</b> and&nbsp;&nbsp;more.",
+                "  keep\n\n   this "
+            ],
+            paragraphs(&output)
+        );
+        assert!(output.contains("<body><p>"));
+
+        let options = SimplifyOptions {
+            collapse_whitespace: false,
+            ..Default::default()
+        };
+        let uncollapsed = simplify_with(html, "en", &options);
+        assert_eq!("  foo\n\n   bar ", paragraphs(&uncollapsed)[0]);
+        assert!(output.len() < uncollapsed.len());
+    }
+
+    #[test]
+    fn collapse_whitespace_inline() {
+        let html = "<html><head></head><body>\
+            <p>The <del>old</del> <ins>new</ins> name is <big>Big</big>, x <math><mi>y</mi></math> z</p>\
+            </body></html>";
+        let output = simplify(html, "en");
+        let paragraph = Html::parse_document(&output)
+            .select(&PARAGRAPH)
+            .next()
+            .unwrap()
+            .inner_html();
+        assert_eq!(
+            "The <del>old</del> <ins>new</ins> name is <big>Big</big>, x <math><mi>y</mi></math> z",
+            paragraph
+        );
+    }
+
+    #[test]
+    fn citations() {
+        let html = r##"<html><head></head><body>
+            <p>Water is H<sub>2</sub>O, and the area is r<sup>2</sup>.<sup class="mw-ref reference" typeof="mw:Extension/ref"><a href="./Water#cite_note-1"><span class="mw-reflink-text">[1]</span></a></sup></p>
+            <p>Unclassed<sup><a href="#cite_note-2">2</a></sup> citations<sup>[a]</sup> are removed too.</p>
+            <p>Only <sup><a href="./Trademark">TM</a></sup> and <sup>[</sup> stay.</p>
+            </body></html>"##;
+
+        let (output, report) = simplify_with_report(html, "en", &Default::default());
+        let paragraphs: Vec<_> = Html::parse_document(&output)
+            .select(&PARAGRAPH)
+            .map(|el| el.inner_html())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "Water is H<sub>2</sub>O, and the area is r<sup>2</sup>.",
+                "Unclassed citations are removed too.",
+                "Only <sup>TM</sup> and <sup>[</sup> stay.",
+            ],
+            paragraphs
+        );
+        assert_eq!(2, report.citations_removed);
+    }
+
+    #[test]
+    fn remove_link_and_meta() {
+        let html = r#"<html><head><meta charset="utf-8"></head><body>
+            <link rel="mw:PageProp/redirect" href="./K%C3%B6ln_Hauptbahnhof">
+            <section><meta property="mw:PageProp/toc"><p>Text<link rel="mw:PageProp/Category" href="./Category:Stations"></p></section>
+            </body></html>"#;
+
+        let output = simplify(html, "en");
+        assert!(!output.contains("<link"), "{output}");
+        assert!(!output.contains("<meta"), "{output}");
+        assert!(output.contains("<p>Text</p>"), "{output}");
+
+        let article = super::extract_article(html, Some("en"), &Default::default()).unwrap();
+        assert_eq!(Some("Köln Hauptbahnhof".to_owned()), article.is_redirect);
+    }
+
+    #[test]
+    fn remove_hidden() {
+        let html = r#"<html><head></head><body>
+            <p>Visible text.</p>
+            <div style="Display: None !important"><p>Hidden metadata.</p></div>
+            <p>Also <span style="color: red; visibility:hidden">hidden</span>visible.</p>
+            <p class="hidden">Hidden paragraph.</p>
+            <div class="mw-collapsible mw-collapsed"><p>Collapsed header</p><div class="mw-collapsible-content"><p>Collapsed content.</p></div></div>
+            <div class="mw-collapsible"><p>Expanded header</p><div class="mw-collapsible-content"><p>Expanded content.</p></div></div>
+            </body></html>"#;
+
+        let (output, report) = simplify_with_report(html, "en", &Default::default());
+        let text: String = Html::parse_document(&output)
+            .root_element()
+            .text()
+            .collect();
+        for visible in [
+            "Visible text.",
+            "Also visible.",
+            "Collapsed header",
+            "Expanded header",
+            "Expanded content.",
+        ] {
+            assert!(text.contains(visible), "{visible:?} not in {output}");
+        }
+        for hidden in ["Hidden", "hidden", "Collapsed content"] {
+            assert!(!text.contains(hidden), "{hidden:?} in {output}");
+        }
+        assert_eq!(4, report.hidden_removed);
+    }
+
+    #[test]
+    fn remove_attrs() {
+        let html = r#"<html><head></head><body>
+            <h2 id="History" style="color: red">History</h2>
+            <p class="lead" style="background: url(data:image/png;base64,iVBORw0KGgo=)">Text with <span class="nowrap" style="white-space: nowrap">a span</span>.</p>
+            </body></html>"#;
+
+        let output = simplify(html, "en");
+        assert!(!output.contains("style="), "{output}");
+        assert!(output.contains(r#"<h2 id="History">"#), "{output}");
+        assert!(output.contains(r#"<p class="lead">"#), "{output}");
+        assert!(output.contains("<span>a span</span>"), "{output}");
+
+        let options = SimplifyOptions {
+            strip_class: vec!["p".to_owned()],
+            ..Default::default()
+        };
+        let output = simplify_with(html, "en", &options);
+        assert!(output.contains("<p>Text"), "{output}");
+        assert!(output.contains(r#"<span class="nowrap">"#), "{output}");
+    }
+
+    #[test]
+    fn image_alt_text() {
+        let html = r#"<html><head></head><body>
+            <p>The view <img src="//upload.wikimedia.org/Castle.jpg" alt=" A castle "> from the north.</p>
+            <p>Decorative <img src="//upload.wikimedia.org/Line.png" alt="">and unlabeled <img src="//upload.wikimedia.org/Dot.png">images.</p>
+            </body></html>"#;
+        let paragraphs = |output: &str| -> Vec<String> {
+            Html::parse_document(output)
+                .select(&PARAGRAPH)
+                .map(|el| el.inner_html())
+                .collect()
+        };
+
+        let options = SimplifyOptions {
+            image_alt_text: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                "The view [A castle] from the north.",
+                "Decorative and unlabeled images."
+            ],
+            paragraphs(&simplify_with(html, "en", &options))
+        );
+
+        assert!(!simplify(html, "en").contains("castle"));
+    }
+
+    #[test]
+    fn keep_code() {
+        // Syntax-highlighted blocks put indentation in their own spans.
+        let html = r#"<html><head></head><body>
+            <p>Call <code>print(  x )</code> to print.</p>
+            <div class="mw-highlight"><pre><span class="k">def</span><span class="w"> </span><span class="nf">f</span>():
+<span class="w">    </span><span class="k">return</span><span class="w">  </span><span class="mi">1</span>
+</pre></div>
+            </body></html>"#;
+        let code = "def f():\n    return  1\n";
+        let text = |output: &str, selector: &str| -> String {
+            Html::parse_document(output)
+                .select(&Selector::parse(selector).unwrap())
+                .next()
+                .unwrap()
+                .text()
+                .collect()
+        };
+
+        let options = SimplifyOptions {
+            keep_code: true,
+            ..Default::default()
+        };
+        let output = simplify_with(html, "en", &options);
+        assert_eq!(code, text(&output, "pre"));
+        assert_eq!("print(  x )", text(&output, "code"));
+
+        let output = simplify(html, "en");
+        assert_eq!("deff():\nreturn1\n", text(&output, "pre"));
+        assert_eq!("print( x )", text(&output, "code"));
+    }
+
+    #[test]
+    fn strip_chars() {
+        let html = "<html><head></head><body>\
+            <p>Zero\u{200B}width and soft\u{00AD}hyphen.</p>\
+            <p>\u{200F}\u{05E2}\u{05D1}\u{05E8}\u{05D9}\u{05EA}\u{200F} (Hebrew)</p>\
+            </body></html>";
+        let paragraphs = |output: &str| -> Vec<String> {
+            Html::parse_document(output)
+                .select(&PARAGRAPH)
+                .map(|el| el.inner_html())
+                .collect()
+        };
+
+        let output = simplify(html, "en");
+        assert_eq!(
+            vec![
+                "Zerowidth and softhyphen.",
+                "\u{200F}\u{05E2}\u{05D1}\u{05E8}\u{05D9}\u{05EA}\u{200F} (Hebrew)"
+            ],
+            paragraphs(&output)
+        );
+
+        let options = SimplifyOptions {
+            strip_bidi: true,
+            ..Default::default()
+        };
+        let output = simplify_with(html, "en", &options);
+        assert_eq!(
+            "\u{05E2}\u{05D1}\u{05E8}\u{05D9}\u{05EA} (Hebrew)",
+            paragraphs(&output)[1]
+        );
+    }
+
+    #[test]
+    fn max_header_level() {
+        let html = r#"<html><head></head><body>
+            <section><h2>History</h2><p>History text.</p>
+                <section><h3>Early years</h3><p>Early text.</p>
+                    <section><h4>Childhood</h4><p>Childhood text.</p>
+                        <section><h5>School</h5><p>School text.</p></section>
+                    </section>
+                </section>
+            </section>
+            <section><h2>References</h2><p>Reference text.</p>
+                <section><h4>Notes</h4><p>Note text.</p></section>
+            </section>
+        </body></html>"#;
+        let options = SimplifyOptions {
+            max_header_level: 3,
+            ..Default::default()
+        };
+
+        let (output, report) = simplify_with_report(html, "en", &options);
+        let document = Html::parse_document(&output);
+        let headers: Vec<String> = document.select(&HEADERS).map(|h| h.inner_html()).collect();
+
+        assert_eq!(vec!["History", "Early years"], headers);
+        assert_eq!(2, report.headers_removed);
+        for text in [
+            "History text.",
+            "Early text.",
+            "Childhood text.",
+            "School text.",
+        ] {
+            assert!(output.contains(text), "{text:?} should be kept");
+        }
+        assert!(!output.contains("Note text."));
+    }
+
+    #[test]
+    fn simplify_report() {
+        let html = r#"<html><head></head><body>
+            <p>Text with <a href="./Link">a link</a> and <a href="./Other">another</a>.</p>
+            <div class="hatnote">See also <a href="./Hatnote">this</a></div>
+            <figure><img src="image.png"><figcaption>Caption</figcaption></figure>
+            <p> </p>
+            <h2>References</h2>
+            <p>Reference</p>
+            <ol><li>Citation</li></ol>
+        </body></html>"#;
+
+        let (_, report) = simplify_with_report(html, "en", &SimplifyOptions::default());
+
+        assert_eq!(
+            SimplifyReport {
+                sections_removed: 1,
+                // Elements and whitespace text between them.
+                section_nodes_removed: 5,
+                headers_removed: 0,
+                denylist_removed: 2,
+                // `head` and whitespace-only `p`.
+                empty_removed: 2,
+                citations_removed: 0,
+                hidden_removed: 0,
+                links_removed: 2,
+                empty_paragraphs_removed: 0,
+                empty_lists_removed: 0,
+                no_text: false,
+            },
+            report
+        );
+        let html = r#"<html><head><title>Only a title</title></head><body>
+            <section><h2>References</h2><p>Reference text.</p></section>
+            <p> <img src="image.png"> </p>
+        </body></html>"#;
+        assert!(
+            simplify_with_report(html, "en", &Default::default())
+                .1
+                .no_text
+        );
+    }
+
+    #[test]
+    fn resolve_urls() {
+        let base = Url::parse("https://de.wikipedia.org/wiki/").unwrap();
+        let resolve = |url: &str| {
+            let mut document =
+                Html::parse_fragment(&format!(r#"<img src="{url}"><a href="{url}">Link</a>"#));
+            super::resolve_urls(&mut document, &base);
+            let img = document
+                .select(&Selector::parse("img").unwrap())
+                .next()
+                .unwrap();
+            let a = document
+                .select(&Selector::parse("a").unwrap())
+                .next()
+                .unwrap();
+            let src = img.value().attr("src").unwrap().to_owned();
+            assert_eq!(Some(src.as_str()), a.value().attr("href"));
+            src
+        };
+
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg",
+            resolve("//upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg")
+        );
+        assert_eq!("https://de.wikipedia.org/wiki/Berlin", resolve("./Berlin"));
+        assert_eq!(
+            "https://de.wikipedia.org/wiki/Berlin",
+            resolve("/wiki/Berlin")
+        );
+        assert_eq!(
+            "https://de.wikipedia.org/wiki/Spezial:ISBN-Suche",
+            resolve("./Spezial:ISBN-Suche")
+        );
+
+        for unchanged in [
+            "https://example.com/page",
+            "http://example.com/",
+            "mailto:someone@example.com",
+            "tel:+49-30-123456",
+            "#cite_note-1",
+        ] {
+            assert_eq!(unchanged, resolve(unchanged));
+        }
+    }
+
+    #[test]
+    fn simplify_resolves_urls() {
+        let options = SimplifyOptions {
+            elements: ElementFilter::new(None::<&str>, ["img"]).unwrap(),
+            ..Default::default()
+        };
+        let resolve = |url: &str| {
+            let html = format!(
+                r#"<html><head><base href="//de.wikipedia.org/wiki/"></head><body><p>Bild <img src="{url}"></p></body></html>"#
+            );
+            let output = simplify_with(&html, "de", &options);
+            let document = Html::parse_document(&output);
+            let img = document
+                .select(&Selector::parse("img").unwrap())
+                .next()
+                .unwrap_or_else(|| panic!("{output}"));
+            img.value().attr("src").unwrap().to_owned()
+        };
+
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg",
+            resolve("//upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg")
+        );
+        assert_eq!(
+            "https://de.wikipedia.org/wiki/Datei:Y.jpg",
+            resolve("./Datei:Y.jpg")
+        );
+        assert_eq!(
+            "https://de.wikipedia.org/wiki/Berlin",
+            resolve("/wiki/Berlin")
+        );
+        for unchanged in [
+            "https://example.com/image.png",
+            "mailto:someone@example.com",
+            "tel:+49-30-123456",
+        ] {
+            assert_eq!(unchanged, resolve(unchanged));
+        }
+    }
+
     #[test]
     fn remove_links() {
         let html = r#"
@@ -0,0 +1,432 @@
+//! Indented html serialization for inspecting and diffing simplified articles.
+use std::io::{self, Write};
+
+use html5ever::{
+    serialize::{AttrRef, Serialize, Serializer, TraversalScope},
+    QualName,
+};
+use scraper::Html;
+
+/// Serialize `html` with each block element on its own line, indented by two spaces.
+///
+/// Text and inline elements are kept on the line of their block, with whitespace collapsed.
+pub fn pretty_print(html: &Html) -> String {
+    pretty_print_with(html, &PrettyOptions::default())
+}
+
+/// Options for [pretty_print_with].
+#[derive(Debug, Clone)]
+pub struct PrettyOptions {
+    /// Number of spaces to indent each level of block elements by.
+    pub indent: usize,
+    /// Wrap text and inline elements onto new lines after this many columns.
+    ///
+    /// Words longer than the width are not split.
+    pub max_inline_width: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            max_inline_width: None,
+        }
+    }
+}
+
+/// Serialize `html` like [pretty_print], with custom indentation and wrapping.
+pub fn pretty_print_with(html: &Html, options: &PrettyOptions) -> String {
+    let mut printer = PrettyPrint::new(Vec::new(), options.clone());
+    html.serialize(&mut printer, TraversalScope::ChildrenOnly(None))
+        .expect("writing to a Vec should not fail");
+    printer.finish().expect("writing to a Vec should not fail");
+
+    String::from_utf8(printer.writer).expect("serialized html should be valid utf-8")
+}
+
+/// Elements that are written inline with the surrounding text.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "dfn", "em", "i", "kbd", "mark",
+    "q", "rp", "rt", "ruby", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u",
+    "var", "wbr",
+];
+
+/// Elements that have no contents or end tag.
+pub(super) const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose whitespace is significant and must be written verbatim.
+const PREFORMATTED_ELEMENTS: &[&str] = &["code", "pre", "textarea"];
+
+fn is_inline(name: &QualName) -> bool {
+    INLINE_ELEMENTS.contains(&name.local.as_ref())
+}
+
+fn is_void(name: &QualName) -> bool {
+    VOID_ELEMENTS.contains(&name.local.as_ref())
+}
+
+fn is_preformatted(name: &QualName) -> bool {
+    PREFORMATTED_ELEMENTS.contains(&name.local.as_ref())
+}
+
+struct PrettyPrint<W: Write> {
+    writer: W,
+    options: PrettyOptions,
+    /// Current indentation in spaces.
+    indent: usize,
+    /// Width of the current line.
+    column: usize,
+    /// If the next content should start on a new line.
+    line_break: bool,
+    /// If the next inline content should be separated by a space.
+    space: bool,
+    /// If anything has been written yet.
+    started: bool,
+    /// Depth of nested preformatted elements, whose contents are written verbatim.
+    preformatted: usize,
+    /// If the start of a `pre` or `textarea` was just written.
+    preformatted_start: bool,
+}
+
+impl<W: Write> PrettyPrint<W> {
+    fn new(writer: W, options: PrettyOptions) -> Self {
+        Self {
+            writer,
+            options,
+            indent: 0,
+            column: 0,
+            line_break: false,
+            space: false,
+            started: false,
+            preformatted: 0,
+            preformatted_start: false,
+        }
+    }
+
+    /// Start a new, indented line.
+    fn new_line(&mut self) -> io::Result<()> {
+        if self.started {
+            self.writer.write_all(b"\n")?;
+        }
+        write!(self.writer, "{:1$}", "", self.indent)?;
+        self.column = self.indent;
+        self.line_break = false;
+        self.space = false;
+        Ok(())
+    }
+
+    /// Write `s` to the current line, starting a new line or separating it with a space if pending.
+    fn write_inline(&mut self, s: &str) -> io::Result<()> {
+        let width = s.chars().count();
+        if self.line_break {
+            self.new_line()?;
+        } else if self.space {
+            let wrap = self
+                .options
+                .max_inline_width
+                .map(|max| self.column + 1 + width > max && self.column > self.indent)
+                .unwrap_or_default();
+            if wrap {
+                self.new_line()?;
+            } else {
+                self.writer.write_all(b" ")?;
+                self.column += 1;
+            }
+            self.space = false;
+        }
+
+        self.writer.write_all(s.as_bytes())?;
+        self.column += width;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Write `s` exactly, without any line breaks or spacing.
+    fn write_verbatim(&mut self, s: &str) -> io::Result<()> {
+        self.writer.write_all(s.as_bytes())?;
+        self.column = match s.rsplit_once('\n') {
+            Some((_, last_line)) => last_line.chars().count(),
+            None => self.column + s.chars().count(),
+        };
+        Ok(())
+    }
+
+    /// Write `s` on its own line.
+    fn write_block(&mut self, s: &str) -> io::Result<()> {
+        self.line_break = true;
+        self.write_inline(s)?;
+        self.line_break = true;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.started {
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Serializer for PrettyPrint<W> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let mut tag = format!("<{}", name.local);
+        for (name, value) in attrs {
+            tag.push_str(&format!(" {}=\"{}\"", name.local, escape(value, true)));
+        }
+        tag.push('>');
+
+        if self.preformatted > 0 {
+            self.write_verbatim(&tag)?;
+        } else if is_inline(&name) {
+            self.write_inline(&tag)?;
+        } else if is_preformatted(&name) {
+            // Contents start directly after the tag.
+            self.line_break = true;
+            self.write_inline(&tag)?;
+        } else {
+            self.write_block(&tag)?;
+            if !is_void(&name) {
+                self.indent += self.options.indent;
+            }
+        }
+
+        if is_preformatted(&name) {
+            self.preformatted += 1;
+        }
+        self.preformatted_start = ["pre", "textarea"].contains(&name.local.as_ref());
+
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        if is_void(&name) {
+            return Ok(());
+        }
+
+        let tag = format!("</{}>", name.local);
+        self.preformatted_start = false;
+        if self.preformatted > 0 {
+            self.write_verbatim(&tag)?;
+            if is_preformatted(&name) {
+                self.preformatted -= 1;
+                if self.preformatted == 0 && !is_inline(&name) {
+                    self.line_break = true;
+                }
+            }
+            Ok(())
+        } else if is_inline(&name) {
+            self.write_inline(&tag)
+        } else {
+            self.indent -= self.options.indent;
+            self.write_block(&tag)
+        }
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if self.preformatted > 0 {
+            // Parsers drop a newline directly after `<pre>`, so add one to keep any in the text.
+            if self.preformatted_start && text.starts_with('\n') {
+                self.write_verbatim("\n")?;
+            }
+            self.preformatted_start = false;
+            return self.write_verbatim(&escape(text, false));
+        }
+
+        // Collapse whitespace, preserving whether the text was separated from its neighbors.
+        // Whitespace between blocks is dropped by the next line break.
+        // Only ascii whitespace is collapsible in html, `&nbsp;` should be kept.
+        if text.starts_with(|c: char| c.is_ascii_whitespace()) && self.started {
+            self.space = true;
+        }
+        for word in text.split_ascii_whitespace() {
+            self.write_inline(&escape(word, false))?;
+            self.space = true;
+        }
+        if !text.ends_with(|c: char| c.is_ascii_whitespace()) {
+            self.space = false;
+        }
+
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.write_block(&format!("<!--{}-->", text))
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.write_block(&format!("<!DOCTYPE {}>", name))
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.write_block(&format!("<?{} {}>", target, data))
+    }
+}
+
+/// Escape text the same way as the html5ever serializer.
+fn escape(text: &str, attr_mode: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '\u{00A0}' => escaped.push_str("&nbsp;"),
+            '"' if attr_mode => escaped.push_str("&quot;"),
+            '<' if !attr_mode => escaped.push_str("&lt;"),
+            '>' if !attr_mode => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scraper::Selector;
+
+    #[test]
+    fn pretty_print() {
+        let html = Html::parse_document(
+            r#"<!DOCTYPE html><html><head><title>Title</title></head><body>
+            <h2 id="History">History</h2><p>Some   <b>bold</b>
+            and <i>italic</i> text &amp; more.<br>After a break.</p>
+            <ul><li>One</li><li>Two</li></ul></body></html>"#,
+        );
+
+        let expected = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>
+      Title
+    </title>
+  </head>
+  <body>
+    <h2 id="History">
+      History
+    </h2>
+    <p>
+      Some <b>bold</b> and <i>italic</i> text &amp; more.<br>After a break.
+    </p>
+    <ul>
+      <li>
+        One
+      </li>
+      <li>
+        Two
+      </li>
+    </ul>
+  </body>
+</html>
+"#;
+
+        assert_eq!(expected, super::pretty_print(&html));
+    }
+
+    const FRAGMENT: &str = r#"<div><p>Some text with a <a href="Link">link</a> and&nbsp;more words.</p><ul><li>Item</li></ul></div>"#;
+
+    #[test]
+    fn indent_width() {
+        let html = Html::parse_fragment(FRAGMENT);
+        let two = super::pretty_print(&html);
+        let four = pretty_print_with(
+            &html,
+            &PrettyOptions {
+                indent: 4,
+                ..Default::default()
+            },
+        );
+
+        let doubled: String = two
+            .lines()
+            .map(|line| {
+                let content = line.trim_start();
+                format!(
+                    "{}{}\n",
+                    " ".repeat(2 * (line.len() - content.len())),
+                    content
+                )
+            })
+            .collect();
+        assert_eq!(doubled, four);
+        assert!(four.contains("and&nbsp;more"));
+
+        let zero = pretty_print_with(
+            &html,
+            &PrettyOptions {
+                indent: 0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(two.lines().count(), zero.lines().count());
+        assert!(zero.lines().all(|line| !line.starts_with(' ')));
+    }
+
+    #[test]
+    fn max_inline_width() {
+        let html = Html::parse_fragment(FRAGMENT);
+        let wrapped = pretty_print_with(
+            &html,
+            &PrettyOptions {
+                indent: 2,
+                max_inline_width: Some(20),
+            },
+        );
+
+        let expected = r#"<html>
+  <div>
+    <p>
+      Some text with
+      a
+      <a href="Link">link</a>
+      and&nbsp;more
+      words.
+    </p>
+    <ul>
+      <li>
+        Item
+      </li>
+    </ul>
+  </div>
+</html>
+"#;
+        assert_eq!(expected, wrapped);
+    }
+
+    #[test]
+    fn preformatted() {
+        let code = "fn main() {\n    println!(\"<hello>\");\n\n\treturn;\n}\n";
+        let html = Html::parse_fragment(&format!(
+            "<div><p>Run <code>cargo  run</code> with:</p><pre>\n\n{}</pre><p>Done.</p></div>",
+            code.replace('<', "&lt;").replace('>', "&gt;")
+        ));
+        let pre = Selector::parse("pre").unwrap();
+        let inline_code = Selector::parse("code").unwrap();
+
+        let pretty = Html::parse_fragment(&super::pretty_print(&html));
+
+        for selector in [&pre, &inline_code] {
+            let text = |html: &Html| {
+                html.select(selector)
+                    .next()
+                    .unwrap()
+                    .text()
+                    .collect::<String>()
+            };
+            assert_eq!(text(&html), text(&pretty));
+        }
+        assert_eq!(
+            format!("\n{code}"),
+            pretty
+                .select(&pre)
+                .next()
+                .unwrap()
+                .text()
+                .collect::<String>()
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! XHTML serialization of simplified articles, for pipelines that need well-formed XML.
+use std::io::{self, Write};
+
+use html5ever::{
+    namespace_url, ns,
+    serialize::{AttrRef, Serialize, Serializer, TraversalScope},
+    Namespace, QualName,
+};
+use scraper::Html;
+
+use super::pretty::VOID_ELEMENTS;
+
+/// Serialize `html` as well-formed XML.
+///
+/// Elements are written with their namespace, void elements like `br` are self-closed, and text and attributes are escaped.
+/// The doctype is dropped, so the `html` element is the single root.
+pub fn to_xhtml(html: &Html) -> String {
+    let mut serializer = XhtmlSerializer {
+        writer: Vec::new(),
+        namespaces: Vec::new(),
+    };
+    html.serialize(&mut serializer, TraversalScope::ChildrenOnly(None))
+        .expect("writing to a Vec should not fail");
+
+    String::from_utf8(serializer.writer).expect("serialized html should be valid utf-8")
+}
+
+struct XhtmlSerializer<W: Write> {
+    writer: W,
+    /// Namespaces of the open elements.
+    namespaces: Vec<Namespace>,
+}
+
+/// Write `text` with the characters that are special in XML escaped, and those that are invalid removed.
+fn write_escaped(writer: &mut impl Write, text: &str, is_attr: bool) -> io::Result<()> {
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        let replacement = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' if is_attr => "&quot;",
+            '\u{0}'..='\u{8}'
+            | '\u{B}'
+            | '\u{C}'
+            | '\u{E}'..='\u{1F}'
+            | '\u{FFFE}'
+            | '\u{FFFF}' => "",
+            _ => continue,
+        };
+        writer.write_all(&text.as_bytes()[start..i])?;
+        writer.write_all(replacement.as_bytes())?;
+        start = i + c.len_utf8();
+    }
+    writer.write_all(&text.as_bytes()[start..])
+}
+
+/// If `name` can be written as an XML attribute name.
+///
+/// Html allows names, like `@click`, that XML doesn't.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or_default()
+        && chars.all(|c| c.is_alphanumeric() || ['-', '_', '.'].contains(&c))
+}
+
+impl<W: Write> Serializer for XhtmlSerializer<W> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        write!(self.writer, "<{}", name.local)?;
+        // Namespace declarations in the html are replaced with ones for the namespaces that are used.
+        if self.namespaces.last() != Some(&name.ns) {
+            write!(self.writer, " xmlns=\"{}\"", name.ns)?;
+        }
+
+        let mut declared_xlink = false;
+        for (attr, value) in attrs {
+            if attr.ns == ns!(xmlns) || (attr.ns == ns!() && attr.local.as_ref() == "xmlns") {
+                continue;
+            }
+            if attr.ns == ns!(xlink) && !declared_xlink {
+                write!(self.writer, " xmlns:xlink=\"{}\"", ns!(xlink))?;
+                declared_xlink = true;
+            }
+            let prefix = match &attr.ns {
+                ns if *ns == ns!(xlink) => "xlink:",
+                ns if *ns == ns!(xml) => "xml:",
+                _ => "",
+            };
+            if !is_xml_name(&attr.local) {
+                continue;
+            }
+            write!(self.writer, " {prefix}{}=\"", attr.local)?;
+            write_escaped(&mut self.writer, value, true)?;
+            self.writer.write_all(b"\"")?;
+        }
+
+        if VOID_ELEMENTS.contains(&name.local.as_ref()) {
+            self.writer.write_all(b"/>")?;
+        } else {
+            self.writer.write_all(b">")?;
+        }
+        self.namespaces.push(name.ns);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        self.namespaces.pop();
+        if VOID_ELEMENTS.contains(&name.local.as_ref()) {
+            return Ok(());
+        }
+        write!(self.writer, "</{}>", name.local)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        write_escaped(&mut self.writer, text, false)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        // XML comments can't contain `--` or end with `-`.
+        // Separate every pair of dashes, replacing `--` alone would leave `---` as `- --`.
+        let mut escaped = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            escaped.push(c);
+            if c == '-' && chars.peek() == Some(&'-') {
+                escaped.push(' ');
+            }
+        }
+        let text = escaped;
+        let space = if text.ends_with('-') { " " } else { "" };
+        write!(self.writer, "<!--{text}{space}-->")
+    }
+
+    fn write_doctype(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        write!(self.writer, "<?{target} {data}?>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_xhtml() {
+        let html = r##"<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml"><head><meta charset="utf-8"><title>A &amp; B</title></head><body>
+<p title='say "hi" & <bye>'>Line<br>break, 1 &lt; 2 &amp; <img src="a.png?x=1&amp;y=2" alt=""> <!-- a -- comment- --> <!-- a --- b --></p>
+<p @click="x">Area is <math xmlns="http://www.w3.org/1998/Math/MathML" alttext="r^{2}"><msup><mi>r</mi><mn>2</mn></msup></math>.</p>
+<svg><use xlink:href="#icon"></use></svg>
+</body></html>"##;
+        let xhtml = super::to_xhtml(&Html::parse_document(html));
+
+        let document =
+            roxmltree::Document::parse(&xhtml).unwrap_or_else(|e| panic!("{e}: {xhtml}"));
+        let root = document.root_element();
+        assert_eq!("html", root.tag_name().name());
+        assert_eq!(
+            Some("http://www.w3.org/1999/xhtml"),
+            root.tag_name().namespace()
+        );
+
+        let find = |name: &str| {
+            document
+                .descendants()
+                .find(|n| n.tag_name().name() == name)
+                .unwrap()
+        };
+        assert_eq!(Some("A & B"), find("title").text());
+        assert_eq!(Some("say \"hi\" & <bye>"), find("p").attribute("title"));
+        assert_eq!(Some("a.png?x=1&y=2"), find("img").attribute("src"));
+        assert_eq!(
+            Some("http://www.w3.org/1998/Math/MathML"),
+            find("math").tag_name().namespace()
+        );
+        assert_eq!(
+            Some("#icon"),
+            find("use").attribute(("http://www.w3.org/1999/xlink", "href"))
+        );
+
+        assert!(
+            xhtml.contains("Line<br/>break, 1 &lt; 2 &amp; <img"),
+            "{xhtml}"
+        );
+        assert!(xhtml.contains("<!-- a - - - b -->"), "{xhtml}");
+        assert!(!xhtml.contains("DOCTYPE"));
+    }
+}
@@ -1,4 +1,9 @@
+pub mod archive;
 pub mod html;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod wm;
 
 #[macro_use]
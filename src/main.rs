@@ -1,48 +1,438 @@
 use std::{
+    ffi::OsString,
+    fmt::{self, Display},
     fs::{self, File},
-    io::{stdin, BufRead, Write},
-    os::unix,
+    io::{self, stdin, BufRead, BufReader, BufWriter, Write},
+    mem,
+    panic::{self, AssertUnwindSafe, UnwindSafe},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
+#[cfg(unix)]
+use std::os::unix::fs::{symlink as symlink_dir, symlink as symlink_file};
+#[cfg(windows)]
+use std::os::windows::fs::{symlink_dir, symlink_file};
+
 use anyhow::{anyhow, bail, Context};
-use clap::{CommandFactory, Parser};
+use clap::{
+    builder::ArgPredicate, parser::ValueSource, CommandFactory, FromArgMatches, Parser, ValueEnum,
+};
+use clap_complete::Shell;
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use scraper::Html;
+use serde_json::json;
 #[macro_use]
 extern crate log;
 
+#[cfg(feature = "sqlite")]
+use om_wikiparser::sqlite::ArticleDb;
 use om_wikiparser::{
-    html::simplify,
-    wm::{parse_wikidata_file, parse_wikipedia_file, Page, WikipediaTitleNorm},
+    archive::ArticleArchive,
+    html::{
+        classify, extract_thumbnail, extract_toc, is_disambiguation, is_redirect, load_config_file,
+        load_sections_file, simplify_with_report, validate, ElementFilter, MathMode, RubyMode,
+        SimplifyOptions, TableMode,
+    },
+    wm::{
+        parse_wikidata_file, parse_wikipedia_file, MatchTracker, Page, PageHeader, WikidataQid,
+        WikipediaTitleNorm,
+    },
 };
 
 /// Extract article HTML from Wikipedia Enterprise HTML dumps.
 ///
-/// Expects an uncompressed dump connected to stdin.
+/// Expects an uncompressed or gzipped dump as a file path, or connected to stdin.
 #[derive(Parser)]
 #[command(version)]
 struct Args {
     /// Directory to write the extracted articles to.
+    ///
+    /// Not used with `--tar-out` or `--sqlite`, where the only positional argument is the dump file.
+    // `--completions` is exclusive, so this isn't required with it, but it needs a value for the struct.
+    #[arg(default_value_if("completions", ArgPredicate::IsPresent, "."))]
+    // It isn't used with `--tar-out` or `--sqlite` either, see [Args::try_parse_args].
+    #[arg(
+        required = false,
+        required_unless_present = "tar_out",
+        default_value_if("tar_out", ArgPredicate::IsPresent, ".")
+    )]
+    #[cfg_attr(
+        feature = "sqlite",
+        arg(
+            required_unless_present = "sqlite",
+            default_value_if("sqlite", ArgPredicate::IsPresent, ".")
+        )
+    )]
     output_dir: PathBuf,
 
+    /// Path to the newline-delimited json dump, read from stdin if not provided.
+    ///
+    /// Gzip-compressed dumps are detected and decompressed automatically.
+    dump_file: Option<PathBuf>,
+
     /// Path to file that contains a Wikidata QID to extract on each line
     /// (e.g. `Q12345`).
+    ///
+    /// Gzip-compressed files are detected and decompressed automatically.
     #[arg(long, help_heading = "FILTERS")]
     wikidata_ids: Option<PathBuf>,
 
     /// Path to file that contains a Wikipedia article url to extract on each line
     /// (e.g. `https://lang.wikipedia.org/wiki/Article_Title`).
+    ///
+    /// Gzip-compressed files are detected and decompressed automatically.
     #[arg(long, help_heading = "FILTERS")]
     wikipedia_urls: Option<PathBuf>,
 
+    /// Skip urls in the `--wikipedia-urls` file with an unknown language code, instead of only warning about them.
+    ///
+    /// Variants like `zh-yue` and `be-tarask`, and `simple`, are accepted.
+    #[arg(long, requires("wikipedia_urls"), help_heading = "FILTERS")]
+    strict_lang: bool,
+
+    /// Comma-separated language codes of the articles to extract (e.g. `en,de,fr`), all languages if not provided.
+    ///
+    /// Articles in other languages are skipped before matching, even if their QID or title was requested.
+    #[arg(long, value_delimiter = ',', help_heading = "FILTERS")]
+    languages: Vec<String>,
+
     /// Append to the provided file path the QIDs of articles matched by title but not QID.
     ///
     /// Use this to save the QIDs of articles you know the url of, but not the QID.
     /// The same path can later be passed to the `--wikidata-ids` option to extract them from another language's dump.
     #[arg(long, requires("wikipedia_urls"))]
     write_new_ids: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row for each redirect of the extracted articles.
+    ///
+    /// Each row is `redirect_lang`, `redirect_name`, and the article's QID, or its title if it has none.
+    #[arg(long)]
+    redirects_out: Option<PathBuf>,
+
+    /// Append to the provided file path the table of contents of each extracted article.
+    ///
+    /// Each line is a json object with the article's `url`, `qid` (or `null`), and `toc`,
+    /// a list of its headers in document order with their `level`, `text`, and `id`.
+    #[arg(long)]
+    toc_out: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row with the main image of each extracted article.
+    ///
+    /// Each row is the article's `lang`, its QID or title if it has none, and the absolute url of the image.
+    /// Articles without a suitable image in their infobox or lead section are skipped.
+    #[arg(long)]
+    thumbnail_out: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row with the kind of each extracted article.
+    ///
+    /// Each row is the article's `lang`, its QID or title if it has none, and one of `article`, `stub`, `disambiguation`, or `redirect`.
+    /// Stubs are articles marked with a stub template, or with little text in paragraphs.
+    #[arg(long)]
+    classify_out: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row with the short description of each extracted article.
+    ///
+    /// Each row is the article's `lang`, its QID or title if it has none, and the description.
+    /// Articles without a description in the dump are skipped.
+    #[arg(long)]
+    description_out: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row for each file and link created.
+    ///
+    /// Each article is a row of `html`, the path of the written file, and its QID or title if it has none.
+    /// It is followed by a row for each title with the `--link-mode`, the path of the link, and the path it links to.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// Append to the provided file path a tab-separated row for each article that could not be processed.
+    ///
+    /// Each row is the `line` and `byte` offset of the article in the dump, its `qid` and `url` if known, the `error_kind`, and the error `message`.
+    /// The kind is `deserialize` for invalid json, `no-text` for articles that are empty after simplification,
+    /// `panic` for bugs in simplification, or `write` for other failures writing the article.
+    #[arg(long)]
+    error_log: Option<PathBuf>,
+
+    /// Also write a `redirect` row to the `--error-log` for each skipped redirect page.
+    ///
+    /// Redirect pages aren't errors, so they are only logged at the debug level by default.
+    #[arg(long, requires = "error_log")]
+    log_redirects: bool,
+
+    /// Log and skip lines of the dump that can't be deserialized, instead of stopping.
+    ///
+    /// The number of skipped lines is logged at the end.
+    #[arg(long)]
+    skip_bad_lines: bool,
+
+    /// Report which requested articles are in the dump without writing anything.
+    ///
+    /// Runs the same matching as a normal extraction (including redirect titles), but skips html simplification and all filesystem writes.
+    /// The requested QIDs and titles that were not found are printed at the end.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Repair broken title links in the output directory, then exit without reading a dump.
+    ///
+    /// Links are broken if what they link to was removed, or is an empty directory.
+    /// Links to an article that is still in the output directory, e.g. after it was moved, are recreated to point to it.
+    /// The others are removed, and a later extraction recreates the links of the articles it writes.
+    #[arg(long, conflicts_with_all = ["dump_file", "dry_run"])]
+    verify_links: bool,
+
+    /// Don't rewrite articles that are the same as the existing files, for incremental updates of the output directory.
+    ///
+    /// The files of unchanged articles keep their modification times.
+    #[arg(long)]
+    skip_unchanged: bool,
+
+    /// Compress the written articles with gzip, as `lang.html.gz` instead of `lang.html`.
+    #[arg(long)]
+    gzip_output: bool,
+
+    /// How to make articles available from their wikipedia title and redirect directories.
+    #[arg(long, value_enum, default_value_t)]
+    link_mode: LinkMode,
+
+    /// How to arrange the articles in the output directory.
+    #[arg(long, value_enum, default_value_t)]
+    layout: Layout,
+
+    /// Path to a SQLite database to write the articles to, instead of the output directory.
+    ///
+    /// Articles are stored in the `articles` table by their QID, or their title if they don't have one, and language.
+    /// The titles that point to them are stored in the `redirects` table.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, conflicts_with_all = ["manifest", "skip_unchanged", "gzip_output", "verify_links", "tar_out", "layout"])]
+    sqlite: Option<PathBuf>,
+
+    /// Path to a tar archive to write the articles to, instead of the output directory.
+    ///
+    /// The archive has the same layout as the output directory, with symlinks for the wikipedia titles.
+    #[arg(long, conflicts_with_all = ["manifest", "skip_unchanged", "gzip_output", "verify_links", "layout"])]
+    tar_out: Option<PathBuf>,
+
+    /// Path to a json file of additional css selectors of elements to remove from, or keep in, articles.
+    ///
+    /// The file has the form `{"deny": ["div.navbox"], "allow": ["div.navbox.keep"]}`.
+    /// Both lists are merged with the built-in ones.
+    #[arg(long)]
+    selectors: Option<PathBuf>,
+
+    /// How to simplify MathML formulas.
+    #[arg(long, value_enum, default_value_t)]
+    math_mode: MathMode,
+
+    /// How to simplify tables.
+    #[arg(long, value_enum, default_value_t)]
+    table_mode: TableMode,
+
+    /// How to simplify ruby pronunciation annotations.
+    #[arg(long, value_enum, default_value_t)]
+    ruby_mode: RubyMode,
+
+    /// Remove headers deeper than this level (e.g. `3` removes `h4` through `h7`), keeping the contents of their sections.
+    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(1..=7))]
+    max_header_level: u8,
+
+    /// Path to a json file to use instead of the built-in `article_processing_config.json`.
+    ///
+    /// Titles that aren't NFC-normalized are normalized with a warning.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Path to a json file of additional section titles to remove for each language.
+    ///
+    /// The file has the same structure as `article_processing_config.json`, e.g. `{"sections_to_remove": {"en": ["Gallery"]}}`.
+    #[arg(long)]
+    sections_file: Option<PathBuf>,
+
+    /// Remove bidirectional text control characters, like left-to-right marks, from text.
+    ///
+    /// Zero-width spaces and soft hyphens are always removed.
+    #[arg(long)]
+    strip_bidi: bool,
+
+    /// Keep the whitespace of `code` elements, and the indentation of syntax-highlighted code blocks.
+    #[arg(long)]
+    keep_code: bool,
+
+    /// Write well-formed XHTML, with self-closed void elements and a single root element, for XML tools.
+    #[arg(long)]
+    xhtml: bool,
+
+    /// Check that each simplified article is well-formed html that doesn't change when parsed again, and skip writing it if not.
+    ///
+    /// Failed articles are logged as errors, and written to the `--error-log`.
+    #[arg(long, conflicts_with = "xhtml")]
+    validate_output: bool,
+
+    /// Replace removed images with their `alt` text in brackets, e.g. `[A castle]`.
+    #[arg(long)]
+    image_alt_text: bool,
+
+    /// Log less, only warnings with `-q` and only errors with `-qq`.
+    ///
+    /// The `RUST_LOG` env variable takes precedence.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    quiet: u8,
+
+    /// Log more, debug messages with `-v` and everything with `-vv`.
+    ///
+    /// The `RUST_LOG` env variable takes precedence.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Format of the log messages written to stderr.
+    #[arg(long, value_enum, default_value_t, env = "OM_LOG_FORMAT")]
+    log_format: LogFormat,
+
+    /// Don't write matched articles that are disambiguation pages.
+    #[arg(long)]
+    skip_disambiguation: bool,
+
+    /// Copy the json lines of matched pages of these kinds to stdout, to audit what is skipped.
+    ///
+    /// Redirect pages are never written, and disambiguation pages are skipped with `--skip-disambiguation`.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    passthrough: Vec<Passthrough>,
+
+    /// Print a completion script for the shell to stdout, then exit.
+    ///
+    /// E.g. `om-wikiparser --completions bash > /etc/bash_completion.d/om-wikiparser`.
+    #[arg(long, exclusive = true)]
+    completions: Option<Shell>,
+}
+
+impl Args {
+    /// Parse the command line arguments, like [Parser::try_parse_from].
+    ///
+    /// Positional arguments are assigned in order, so without an output directory
+    /// a single positional argument is moved to the dump file.
+    fn try_parse_args<I, T>(itr: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(itr)?;
+        let mut args = Self::from_arg_matches(&matches)?;
+
+        if !args.has_output_dir()
+            && matches.value_source("output_dir") == Some(ValueSource::CommandLine)
+        {
+            if args.dump_file.is_some() {
+                return Err(Self::command().error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "an output directory can't be used with --tar-out or --sqlite",
+                ));
+            }
+            args.dump_file = Some(mem::replace(&mut args.output_dir, PathBuf::from(".")));
+        }
+
+        Ok(args)
+    }
+
+    /// The path of an output file, which isn't written with `--dry-run`.
+    fn output_path<'a>(&self, path: &'a Option<PathBuf>) -> Option<&'a Path> {
+        path.as_deref().filter(|_| !self.dry_run)
+    }
+
+    /// If the articles are written to `output_dir`, instead of a single file.
+    fn has_output_dir(&self) -> bool {
+        #[cfg(feature = "sqlite")]
+        if self.sqlite.is_some() {
+            return false;
+        }
+        self.tar_out.is_none()
+    }
+}
+
+/// Kinds of pages copied to stdout by `--passthrough`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Passthrough {
+    Redirect,
+    Disambiguation,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum LinkMode {
+    /// Symlink the title directories to the main article directory.
+    #[default]
+    Symlink,
+    /// Hard link the article file into each title directory.
+    Hardlink,
+    /// Copy the article file into each title directory.
+    ///
+    /// For filesystems that support neither symlinks nor hard links.
+    Copy,
+}
+
+/// How the articles are arranged in the output directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Layout {
+    /// `wikidata/{qid}/{lang}.html`, linked from `{lang}.wikipedia.org/wiki/{title}` directories, as used by the map generator.
+    ///
+    /// Articles without a QID are written to the directory of their first title.
+    #[default]
+    Generator,
+    /// `{qid}.{lang}.html` files, without links for titles.
+    ///
+    /// Articles without a QID are skipped with an error.
+    FlatQid,
+    /// `{lang}/{title}.html` files, linked from the files of the other requested titles.
+    ByLang,
+}
+
+/// Summary of a run over the dump.
+#[derive(Debug, Default)]
+struct Matches {
+    /// Requested QIDs and titles that were found in the dump, reported with `--dry-run`.
+    tracker: MatchTracker,
+    /// Lines skipped with `--skip-bad-lines`.
+    skipped_lines: usize,
+    /// If the run was stopped by [INTERRUPTED] before the end of the dump.
+    interrupted: bool,
+    /// Articles that weren't written with `--skip-unchanged`.
+    unchanged: usize,
+}
+
+/// Maximum number of unseen QIDs/titles to list in the `--dry-run` report.
+const UNSEEN_REPORT_LIMIT: usize = 20;
+
+impl Matches {
+    /// Print the matched counts and the requested items that were never seen.
+    fn report(&self) {
+        let tracker = &self.tracker;
+        println!(
+            "Matched {} of {} requested QIDs",
+            tracker.matched_qids().len(),
+            tracker.wikidata_ids().len()
+        );
+        let mut unseen: Vec<_> = tracker.unmatched_qids().collect();
+        unseen.sort();
+        print_unseen(&unseen);
+
+        println!(
+            "Matched {} of {} requested titles",
+            tracker.matched_titles().len(),
+            tracker.wikipedia_titles().len()
+        );
+        let mut unseen: Vec<_> = tracker.unmatched_titles().collect();
+        unseen.sort();
+        print_unseen(&unseen);
+    }
+}
+
+fn print_unseen(unseen: &[impl std::fmt::Debug]) {
+    for item in unseen.iter().take(UNSEEN_REPORT_LIMIT) {
+        println!("  unseen: {:?}", item);
+    }
+    if unseen.len() > UNSEEN_REPORT_LIMIT {
+        println!("  ...and {} more", unseen.len() - UNSEEN_REPORT_LIMIT);
+    }
 }
 
-/// Determine the directory to write the article contents to, create it, and create any necessary symlinks to it.
+/// Determine the directory to write the article contents to and create it.
 fn create_article_dir(
     base: impl AsRef<Path>,
     page: &Page,
@@ -70,7 +460,7 @@ fn create_article_dir(
                 .get_dir(base.to_owned())
         }
         Some(qid) => {
-            // Otherwise use wikidata as main directory and symlink from wikipedia titles.
+            // Otherwise use wikidata as main directory and link from wikipedia titles.
             qid.get_dir(base.to_owned())
         }
     };
@@ -82,10 +472,44 @@ fn create_article_dir(
     fs::create_dir_all(&main_dir)
         .with_context(|| format!("creating main directory {:?}", &main_dir))?;
 
-    // Write symlinks to main directory.
+    Ok(main_dir)
+}
+
+/// Make the article file available from each of the wikipedia title directories.
+///
+/// In [LinkMode::Symlink] the title directories link to the main directory,
+/// otherwise they are real directories with the article file linked or copied into them.
+fn create_redirects(
+    base: impl AsRef<Path>,
+    main_dir: &Path,
+    article_file: &Path,
+    redirects: impl IntoIterator<Item = WikipediaTitleNorm>,
+    link_mode: LinkMode,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let base = base.as_ref();
+    let mut links = Vec::new();
+
     for title in redirects {
         let wikipedia_dir = title.get_dir(base.to_owned());
 
+        // A malformed dump can list the page's own title as a redirect,
+        // which would replace the article with a link to itself.
+        if wikipedia_dir == main_dir {
+            debug!("Skipping redirect to itself {:?}", wikipedia_dir);
+            continue;
+        }
+        // Titles can contain `/`, so one can be a subdirectory of the other.
+        // Linking between them would create a cycle or remove the article.
+        if link_mode == LinkMode::Symlink
+            && (wikipedia_dir.starts_with(main_dir) || main_dir.starts_with(&wikipedia_dir))
+        {
+            debug!(
+                "Skipping redirect {:?} nested with main directory {:?}",
+                wikipedia_dir, main_dir
+            );
+            continue;
+        }
+
         // Build required directory.
         //
         // Possible states from previous run:
@@ -93,14 +517,19 @@ fn create_article_dir(
         // - Exists, is a directory
         // - Exists, is a valid symlink to correct location
         // - Exists, is a valid symlink to incorrect location
-        if wikipedia_dir.exists() {
-            if wikipedia_dir.is_symlink() {
-                // Only replace if not valid
-                if fs::read_link(&wikipedia_dir)? == main_dir {
-                    continue;
-                }
-                fs::remove_file(&wikipedia_dir)?;
-            } else {
+        // - Exists, is a dangling symlink to a removed location
+        //
+        // `exists` follows symlinks, so check for a symlink first to replace dangling ones.
+        if wikipedia_dir.is_symlink() {
+            // Only replace if not valid
+            if link_mode == LinkMode::Symlink && fs::read_link(&wikipedia_dir)? == main_dir {
+                links.push((wikipedia_dir, main_dir.to_owned()));
+                continue;
+            }
+            fs::remove_file(&wikipedia_dir)
+                .with_context(|| format!("removing old link {:?}", wikipedia_dir))?;
+        } else if wikipedia_dir.exists() {
+            if link_mode == LinkMode::Symlink {
                 fs::remove_dir_all(&wikipedia_dir)?;
             }
         } else {
@@ -110,152 +539,2295 @@ fn create_article_dir(
                 .with_context(|| format!("creating wikipedia directory {:?}", parent_dir))?;
         }
 
-        unix::fs::symlink(&main_dir, &wikipedia_dir).with_context(|| {
-            format!(
-                "creating symlink from {:?} to {:?}",
-                wikipedia_dir, main_dir
-            )
-        })?;
+        if link_mode == LinkMode::Symlink {
+            symlink_dir(main_dir, &wikipedia_dir).with_context(|| {
+                format!(
+                    "creating symlink from {:?} to {:?}",
+                    wikipedia_dir, main_dir
+                )
+            })?;
+            links.push((wikipedia_dir, main_dir.to_owned()));
+            continue;
+        }
+
+        fs::create_dir_all(&wikipedia_dir)
+            .with_context(|| format!("creating wikipedia directory {:?}", wikipedia_dir))?;
+
+        let redirect_file = wikipedia_dir.join(article_file.file_name().unwrap());
+        if redirect_file.exists() {
+            fs::remove_file(&redirect_file)?;
+        }
+
+        if link_mode == LinkMode::Hardlink {
+            fs::hard_link(article_file, &redirect_file).with_context(|| {
+                format!(
+                    "creating hard link from {:?} to {:?}",
+                    redirect_file, article_file
+                )
+            })?;
+        } else {
+            fs::copy(article_file, &redirect_file)
+                .with_context(|| format!("copying {:?} to {:?}", article_file, redirect_file))?;
+        }
+        links.push((redirect_file, article_file.to_owned()));
     }
 
-    Ok(main_dir)
+    Ok(links)
 }
 
-/// Write selected article to disk.
+/// Repair the links in `output_dir` that are dangling, or that point to an empty directory.
 ///
-/// - Write page contents to wikidata page (`wikidata.org/wiki/QXXX/lang.html`).
-/// - If the page has no wikidata qid, write contents to wikipedia location (`lang.wikipedia.org/wiki/article_title/lang.html`).
-/// - Create links from all wikipedia urls and redirects (`lang.wikipedia.org/wiki/a_redirect -> wikidata.org/wiki/QXXX`).
-fn write(
-    base: impl AsRef<Path>,
-    page: &Page,
-    redirects: impl IntoIterator<Item = WikipediaTitleNorm>,
-) -> anyhow::Result<()> {
-    let article_dir = create_article_dir(base, page, redirects)?;
+/// A link is recreated if the article it points to is found in `output_dir` at the same path
+/// below the `wikidata` or wikipedia directories, using its absolute path.
+/// The other articles can't be recreated without the dump,
+/// so their links are removed for the next extraction to create them again.
+/// Returns the number of links repaired and removed.
+fn repair_broken_links(output_dir: &Path) -> anyhow::Result<(usize, usize)> {
+    let output_dir = output_dir
+        .canonicalize()
+        .with_context(|| format!("resolving output directory {:?}", output_dir))?;
+    let mut repaired = 0;
+    let mut removed = 0;
+    let mut dirs = vec![output_dir.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("reading directory {:?}", dir))? {
+            let entry = entry.with_context(|| format!("reading directory {:?}", dir))?;
+            let path = entry.path();
+            // Doesn't follow symlinks, so links to other directories aren't visited twice.
+            let file_type = entry
+                .file_type()
+                .with_context(|| format!("reading file type of {:?}", path))?;
+            if file_type.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if !file_type.is_symlink() {
+                continue;
+            }
+            if !is_broken_link(&path) {
+                continue;
+            }
 
-    // Write html to determined file.
-    let mut filename = article_dir;
-    filename.push(&page.in_language.identifier);
-    filename.set_extension("html");
+            let target = fs::read_link(&path).ok();
+            fs::remove_file(&path).with_context(|| format!("removing broken link {:?}", path))?;
 
-    debug!("{:?}: {:?}", page.name, filename);
+            let article = target
+                .as_deref()
+                .and_then(|target| article_in(&output_dir, target))
+                .filter(|article| !is_broken_link(article));
+            let Some(article) = article else {
+                info!("Removed broken link {:?}", path);
+                removed += 1;
+                continue;
+            };
 
-    if filename.exists() {
-        debug!("Overwriting existing file");
+            info!("Repairing broken link {:?} to {:?}", path, article);
+            let link = if article.is_dir() {
+                symlink_dir(&article, &path)
+            } else {
+                symlink_file(&article, &path)
+            };
+            link.with_context(|| format!("creating symlink from {:?} to {:?}", path, article))?;
+            repaired += 1;
+        }
     }
 
-    let html = simplify(&page.article_body.html, &page.in_language.identifier);
-
-    let mut file =
-        File::create(&filename).with_context(|| format!("creating html file {:?}", filename))?;
-    file.write_all(html.as_bytes())
-        .with_context(|| format!("writing html file {:?}", filename))?;
+    Ok((repaired, removed))
+}
 
-    Ok(())
+/// If the link at `path` is dangling, or points to an empty directory.
+fn is_broken_link(path: &Path) -> bool {
+    match fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_none(),
+        // A link to a file, like a hard-linked article, isn't broken.
+        Err(_) => !path.exists(),
+    }
 }
 
-fn main() -> anyhow::Result<()> {
-    // Use info level by default, load overrides from `RUST_LOG` env variable.
-    // See https://docs.rs/env_logger/latest/env_logger/index.html#example
-    env_logger::Builder::new()
-        .filter_level(log::LevelFilter::Info)
-        .parse_default_env()
-        .try_init()?;
+/// The path in `output_dir` of the article that a link to `target` was created for,
+/// from the components of `target` after the last `wikidata` or wikipedia directory.
+fn article_in(output_dir: &Path, target: &Path) -> Option<PathBuf> {
+    let components: Vec<_> = target.components().collect();
+    let start = components.iter().rposition(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name == "wikidata" || name.ends_with(".wikipedia.org")
+    })?;
+    let article = output_dir.join(components[start..].iter().collect::<PathBuf>());
+    article.exists().then_some(article)
+}
 
-    let args = Args::parse();
+/// Paths created by [write].
+struct Written {
+    /// The article html file.
+    file: PathBuf,
+    /// Each title directory (or file, when not symlinking) and the main directory (or file) it links to.
+    links: Vec<(PathBuf, PathBuf)>,
+    /// If the file already had the same contents and wasn't written, see `--skip-unchanged`.
+    unchanged: bool,
+}
 
-    if args.wikidata_ids.is_none() && args.wikipedia_urls.is_none() {
-        let mut cmd = Args::command();
-        cmd.error(
-            clap::error::ErrorKind::MissingRequiredArgument,
-            "one or both of --wikidata-ids and --wikipedia-urls is required",
-        )
-        .exit()
-    }
+/// Determine the file to write the article to in `args.layout`, and create its directory.
+///
+/// Titles used for the file are taken from `redirects`, the rest are left to be linked to it.
+fn create_article_file(
+    args: &Args,
+    page: &Page,
+    redirects: &mut impl Iterator<Item = WikipediaTitleNorm>,
+) -> anyhow::Result<PathBuf> {
+    let base = &args.output_dir;
+    let lang = &page.in_language.identifier;
+    let extension = html_extension(args.gzip_output);
 
-    let wikipedia_titles = if let Some(path) = args.wikipedia_urls {
-        info!("Loading article urls from {path:?}");
-        let urls = parse_wikipedia_file(path)?;
-        debug!("Parsed {} unique article urls", urls.len());
-        urls
-    } else {
-        Default::default()
+    let filename = match args.layout {
+        Layout::Generator => {
+            create_article_dir(base, page, redirects)?.join(format!("{lang}.{extension}"))
+        }
+        Layout::FlatQid => {
+            let qid = page
+                .wikidata()
+                .ok_or_else(|| anyhow!("No QID for page {:?} in flat-qid layout", page.name))?;
+            base.join(format!("{qid}.{lang}.{extension}"))
+        }
+        Layout::ByLang => {
+            let title = page
+                .title()
+                .with_context(|| format!("parsing title for page {:?}", page.name))?;
+            title_file(base, &title, extension)
+        }
     };
 
-    let wikidata_ids = if let Some(path) = args.wikidata_ids {
-        info!("Loading wikidata ids from {path:?}");
-        let ids = parse_wikidata_file(path)?;
-        debug!("Parsed {} unique wikidata ids", ids.len());
-        ids
+    // Titles can contain `/`, so ensure necessary subdirs exist.
+    let parent_dir = filename.parent().unwrap();
+    fs::create_dir_all(parent_dir)
+        .with_context(|| format!("creating article directory {:?}", parent_dir))?;
+
+    Ok(filename)
+}
+
+/// The extension of article files, including `.gz` if they are compressed.
+fn html_extension(gzip: bool) -> &'static str {
+    if gzip {
+        "html.gz"
     } else {
-        Default::default()
-    };
+        "html"
+    }
+}
 
-    let mut write_new_ids = args
-        .write_new_ids
-        .as_ref()
-        .map(|p| File::options().create(true).append(true).open(p))
-        .transpose()?;
+/// The article file of `title` in [Layout::ByLang].
+///
+/// Titles can contain `.`, so the extension is appended instead of set with [Path::set_extension].
+fn title_file(base: &Path, title: &WikipediaTitleNorm, extension: &str) -> PathBuf {
+    base.join(title.lang())
+        .join(format!("{}.{extension}", title.name()))
+}
 
-    if !args.output_dir.is_dir() {
-        bail!("output dir {:?} does not exist", args.output_dir)
+/// Make the article file available from the files of each title in [Layout::ByLang].
+fn create_title_links(
+    base: &Path,
+    article_file: &Path,
+    redirects: impl IntoIterator<Item = WikipediaTitleNorm>,
+    args: &Args,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let extension = html_extension(args.gzip_output);
+    let mut links = Vec::new();
+
+    for title in redirects {
+        let link = title_file(base, &title, extension);
+        if link == article_file {
+            debug!("Skipping redirect to itself {:?}", link);
+            continue;
+        }
+
+        // `exists` follows symlinks, so check for a symlink first to replace dangling ones.
+        if link.is_symlink() || link.exists() {
+            fs::remove_file(&link).with_context(|| format!("removing old link {:?}", link))?;
+        } else {
+            let parent_dir = link.parent().unwrap();
+            fs::create_dir_all(parent_dir)
+                .with_context(|| format!("creating wikipedia directory {:?}", parent_dir))?;
+        }
+
+        match args.link_mode {
+            LinkMode::Symlink => symlink_file(article_file, &link).with_context(|| {
+                format!("creating symlink from {:?} to {:?}", link, article_file)
+            })?,
+            LinkMode::Hardlink => fs::hard_link(article_file, &link).with_context(|| {
+                format!("creating hard link from {:?} to {:?}", link, article_file)
+            })?,
+            LinkMode::Copy => {
+                fs::copy(article_file, &link)
+                    .with_context(|| format!("copying {:?} to {:?}", article_file, link))?;
+            }
+        }
+        links.push((link, article_file.to_owned()));
     }
 
-    info!("Processing dump");
-    let dump = stdin().lock();
+    Ok(links)
+}
 
-    // TODO: Compare different deserialization methods.
-    // The docs warn against using a reader directly, and it's slower than tar can decompress the dump.
-    // let stream = serde_json::Deserializer::from_reader(dump).into_iter::<Page>();
-    let stream = dump.lines().map(|r| {
-        r.map_err(anyhow::Error::new)
-            .and_then(|s| serde_json::from_str::<Page>(&s).map_err(anyhow::Error::new))
-    });
+/// Write selected article to disk.
+///
+/// - Write page contents to the file for its QID or title in `--layout`, e.g. `wikidata/QXXX/lang.html`.
+/// - Create links from all wikipedia urls and redirects (`lang.wikipedia.org/wiki/a_redirect -> wikidata/QXXX`).
+/// - If `--gzip-output` is set, compress the contents and write to `lang.html.gz` instead.
+fn write(
+    args: &Args,
+    page: &Page,
+    redirects: impl IntoIterator<Item = WikipediaTitleNorm>,
+    simplify_options: &SimplifyOptions,
+) -> anyhow::Result<Written> {
+    let base = &args.output_dir;
+    let gzip = args.gzip_output;
+    let mut redirects = redirects.into_iter();
+    let filename = create_article_file(args, page, &mut redirects)?;
 
-    for page in stream {
-        let page = page?;
+    debug!("{:?}: {:?}", page.name, filename);
 
-        let qid = page.wikidata();
+    if filename.exists() {
+        debug!("Overwriting existing file");
+    }
 
-        let is_wikidata_match = qid
-            .as_ref()
-            .map(|qid| wikidata_ids.contains(qid))
-            .unwrap_or_default();
+    let html = simplify_page(args, page, simplify_options)?;
 
-        let matching_titles = if wikipedia_titles.is_empty() {
-            Default::default()
-        } else {
-            page.all_titles()
-                .filter_map(|r| {
-                    r.map(Some).unwrap_or_else(|e| {
-                        warn!("Could not parse title for {:?}: {:#}", &page.name, e);
-                        None
-                    })
-                })
-                .filter(|t| wikipedia_titles.contains(t))
-                .collect::<Vec<_>>()
-        };
+    let contents = if gzip {
+        // The gzip header doesn't include a timestamp, so the same html is compressed to the same bytes.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(html.as_bytes())
+            .and_then(|_| encoder.finish())
+            .context("compressing html")?
+    } else {
+        html.into_bytes()
+    };
 
-        if !is_wikidata_match && matching_titles.is_empty() {
-            continue;
+    let unchanged = args.skip_unchanged
+        && fs::read(&filename)
+            .map(|existing| existing == contents)
+            .unwrap_or_default();
+    if unchanged {
+        debug!("Skipping unchanged file");
+    } else {
+        fs::write(&filename, &contents)
+            .with_context(|| format!("writing html file {:?}", filename))?;
+    }
+
+    let links = match args.layout {
+        Layout::Generator => {
+            let article_dir = filename.parent().unwrap();
+            create_redirects(base, article_dir, &filename, redirects, args.link_mode)?
         }
+        Layout::FlatQid => Vec::new(),
+        Layout::ByLang => create_title_links(base, &filename, redirects, args)?,
+    };
 
-        if let (Some(f), Some(qid)) = (&mut write_new_ids, &qid) {
-            if !is_wikidata_match && !matching_titles.is_empty() {
-                debug!("Writing new id {} for article {:?}", qid, page.name);
-                writeln!(f, "{}", qid).with_context(|| {
-                    format!(
-                        "writing new id to file {:?}",
-                        args.write_new_ids.as_ref().unwrap()
-                    )
-                })?;
-            }
+    Ok(Written {
+        file: filename,
+        links,
+        unchanged,
+    })
+}
+
+/// Where the extracted articles are written.
+enum Output {
+    /// Files and links in `output_dir`.
+    Dir,
+    Tar(ArticleArchive<BufWriter<File>>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(ArticleDb),
+}
+
+impl Output {
+    fn open(args: &Args) -> anyhow::Result<Self> {
+        if let Some(path) = &args.tar_out {
+            let file =
+                File::create(path).with_context(|| format!("creating archive {:?}", path))?;
+            return Ok(Self::Tar(ArticleArchive::new(BufWriter::new(file))));
         }
+        #[cfg(feature = "sqlite")]
+        if let Some(path) = &args.sqlite {
+            return Ok(Self::Sqlite(ArticleDb::open(path)?));
+        }
+        Ok(Self::Dir)
+    }
 
-        if let Err(e) = write(&args.output_dir, &page, matching_titles) {
-            error!("Error writing article {:?}: {:#}", page.name, e);
+    /// Write anything that is still buffered.
+    fn finish(self, args: &Args) -> anyhow::Result<()> {
+        match self {
+            Self::Dir => {}
+            Self::Tar(archive) => archive
+                .finish()
+                .and_then(|mut writer| writer.flush().map_err(Into::into))
+                .with_context(|| format!("writing archive {:?}", args.tar_out.as_ref().unwrap()))?,
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(db) => db
+                .finish()
+                .with_context(|| format!("writing database {:?}", args.sqlite.as_ref().unwrap()))?,
         }
+        Ok(())
     }
+}
 
-    Ok(())
+/// Simplify the html of `page`, converting a panic into an error.
+///
+/// Articles without any text left are also an error, and with `--validate-output` so is html that isn't well-formed.
+fn simplify_page(
+    args: &Args,
+    page: &Page,
+    simplify_options: &SimplifyOptions,
+) -> anyhow::Result<String> {
+    // A bug in simplification for one article shouldn't stop the whole dump.
+    // Nothing is shared across articles during simplification, so it is safe to continue after a panic.
+    let (html, report) = catch_panic(AssertUnwindSafe(|| {
+        simplify_with_report(
+            &page.article_body.html,
+            &page.in_language.identifier,
+            simplify_options,
+        )
+    }))
+    .with_context(|| format!("simplifying article {}", describe(page)))?;
+
+    if report.no_text {
+        return Err(anyhow::Error::new(ArticleError {
+            kind: ErrorKind::NoText,
+            message: format!(
+                "article {} has no text after simplification",
+                describe(page)
+            ),
+        }));
+    }
+
+    if args.validate_output {
+        validate(&html).with_context(|| format!("validating article {}", describe(page)))?;
+    }
+
+    Ok(html)
+}
+
+/// Identify `page` in error messages by its title, QID, and url.
+fn describe(page: &Page) -> String {
+    let qid = page
+        .main_entity
+        .as_ref()
+        .map(|e| e.identifier.as_str())
+        .unwrap_or("no QID");
+    format!("{:?} ({qid}, {})", page.name, page.url)
+}
+
+/// Run `f`, converting a panic into an error with its message.
+fn catch_panic<T>(f: impl FnOnce() -> T + UnwindSafe) -> anyhow::Result<T> {
+    panic::catch_unwind(f).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic payload");
+        anyhow::Error::new(ArticleError {
+            kind: ErrorKind::Panic,
+            message: format!("panicked: {message}"),
+        })
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines, with colors in a terminal.
+    #[default]
+    Pretty,
+    /// `key=value` pairs, see <https://brandur.org/logfmt>.
+    Logfmt,
+    /// A json object on each line.
+    Json,
+}
+
+/// Get the default log level from the number of `--quiet` and `--verbose` flags.
+///
+/// Starts at info, and each flag moves one level down to error or up to trace.
+fn log_level(quiet: u8, verbose: u8) -> log::LevelFilter {
+    use log::LevelFilter::*;
+    const LEVELS: [log::LevelFilter; 5] = [Error, Warn, Info, Debug, Trace];
+    const DEFAULT: i16 = 2;
+
+    let index = DEFAULT + i16::from(verbose) - i16::from(quiet);
+    LEVELS[index.clamp(0, LEVELS.len() as i16 - 1) as usize]
+}
+
+/// Configure a logger that writes messages at or above `level` in `format`.
+///
+/// Overrides are loaded from the `RUST_LOG` env variable.
+/// See https://docs.rs/env_logger/latest/env_logger/index.html#example
+fn logger(format: LogFormat, level: log::LevelFilter) -> env_logger::Builder {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level).parse_default_env();
+
+    match format {
+        LogFormat::Pretty => {}
+        LogFormat::Logfmt => {
+            builder.format(|buf, record| {
+                writeln!(
+                    buf,
+                    "ts={} level={} target={} msg={:?}",
+                    buf.timestamp(),
+                    record.level().as_str().to_lowercase(),
+                    record.target(),
+                    record.args().to_string()
+                )
+            });
+        }
+        LogFormat::Json => {
+            builder.format(|buf, record| {
+                let line = json!({
+                    "ts": buf.timestamp().to_string(),
+                    "level": record.level().as_str().to_lowercase(),
+                    "target": record.target(),
+                    "msg": record.args().to_string(),
+                });
+                writeln!(buf, "{}", line)
+            });
+        }
+    }
+
+    builder
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit());
+
+    if let Some(shell) = args.completions {
+        clap_complete::generate(
+            shell,
+            &mut Args::command(),
+            env!("CARGO_BIN_NAME"),
+            &mut io::stdout(),
+        );
+        return Ok(());
+    }
+
+    logger(args.log_format, log_level(args.quiet, args.verbose)).try_init()?;
+
+    if !args.verify_links && args.wikidata_ids.is_none() && args.wikipedia_urls.is_none() {
+        let mut cmd = Args::command();
+        cmd.error(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            "one or both of --wikidata-ids and --wikipedia-urls is required",
+        )
+        .exit()
+    }
+
+    if args.verify_links {
+        let (repaired, removed) = repair_broken_links(&args.output_dir)?;
+        info!("Repaired {repaired} and removed {removed} broken links");
+        return Ok(());
+    }
+
+    // Finish the current article instead of leaving a truncated file.
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed))
+        .context("setting interrupt handler")?;
+
+    let dump = open_dump(args.dump_file.as_deref())?;
+
+    let matches = run(&args, dump)?;
+    if matches.interrupted {
+        // The conventional exit code for SIGINT.
+        std::process::exit(130);
+    }
+
+    Ok(())
+}
+
+/// Open `path` for appending, creating it if it doesn't exist.
+///
+/// Each article's rows are written to the outputs with a single `write_all` call,
+/// so that runs appending to the same files concurrently don't interleave.
+fn open_append(path: &Path, what: &str) -> anyhow::Result<File> {
+    File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening {what} {:?}", path))
+}
+
+/// The QID of the page, or its title if it doesn't have one, that the rows of the outputs point to.
+fn row_target(page: &Page, qid: Option<&WikidataQid>) -> String {
+    match qid {
+        Some(qid) => qid.to_string(),
+        None => page.name.clone(),
+    }
+}
+
+/// Append a `lang\tredirect\ttarget` row for each of the page's redirects.
+fn write_redirects(file: &mut File, page: &Page, qid: Option<&WikidataQid>) -> io::Result<()> {
+    let target = row_target(page, qid);
+    let rows: String = page
+        .redirects
+        .iter()
+        .map(|r| format!("{}\t{}\t{}\n", page.in_language.identifier, r.name, target))
+        .collect();
+
+    file.write_all(rows.as_bytes())
+}
+
+/// Append a tab-separated row for each path created for the page.
+///
+/// The article is `html\tpath\ttarget`, where target is the QID or title like in [row_target],
+/// followed by a `link_mode\tlink_path\tlinked_path` row for each title.
+fn write_manifest(
+    file: &mut File,
+    page: &Page,
+    qid: Option<&WikidataQid>,
+    written: &Written,
+    link_mode: LinkMode,
+) -> io::Result<()> {
+    let target = row_target(page, qid);
+    let link_mode = link_mode
+        .to_possible_value()
+        .expect("no variants are skipped");
+
+    let mut rows = format!("html\t{}\t{}\n", written.file.display(), target);
+    for (link, linked) in &written.links {
+        rows.push_str(&format!(
+            "{}\t{}\t{}\n",
+            link_mode.get_name(),
+            link.display(),
+            linked.display()
+        ));
+    }
+
+    file.write_all(rows.as_bytes())
+}
+
+/// Append a json line with the page's table of contents.
+fn write_toc(
+    file: &mut File,
+    page: &Page,
+    qid: Option<&WikidataQid>,
+    document: &Html,
+) -> io::Result<()> {
+    let mut row = serde_json::to_string(&json!({
+        "url": page.url,
+        "qid": qid.map(ToString::to_string),
+        "toc": extract_toc(document),
+    }))?;
+    row.push('\n');
+
+    file.write_all(row.as_bytes())
+}
+
+/// Append a `lang\ttarget\turl` row with the page's thumbnail, if it has one.
+fn write_thumbnail(
+    file: &mut File,
+    page: &Page,
+    qid: Option<&WikidataQid>,
+    document: &Html,
+) -> io::Result<()> {
+    let Some(url) = extract_thumbnail(document) else {
+        return Ok(());
+    };
+    let target = row_target(page, qid);
+
+    let row = format!("{}\t{}\t{}\n", page.in_language.identifier, target, url);
+
+    file.write_all(row.as_bytes())
+}
+
+fn write_description(file: &mut File, page: &Page, qid: Option<&WikidataQid>) -> io::Result<()> {
+    let Some(description) = &page.description else {
+        return Ok(());
+    };
+    let target = row_target(page, qid);
+    // Keep each description on a single row.
+    let description = description.replace(['\t', '\n', '\r'], " ");
+
+    let row = format!(
+        "{}\t{}\t{}\n",
+        page.in_language.identifier, target, description
+    );
+
+    file.write_all(row.as_bytes())
+}
+
+fn write_kind(
+    file: &mut File,
+    page: &Page,
+    qid: Option<&WikidataQid>,
+    document: &Html,
+) -> io::Result<()> {
+    let target = row_target(page, qid);
+
+    let row = format!(
+        "{}\t{}\t{}\n",
+        page.in_language.identifier,
+        target,
+        classify(document)
+    );
+
+    file.write_all(row.as_bytes())
+}
+
+/// The `error_kind` column of the `--error-log`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorKind {
+    /// A line of the dump that couldn't be deserialized.
+    Deserialize,
+    /// A requested page that is a redirect, and isn't written.
+    Redirect,
+    /// An article without any text after simplification.
+    NoText,
+    /// A panic while simplifying an article.
+    Panic,
+    /// Any other error while writing an article.
+    Write,
+}
+
+impl ErrorKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Deserialize => "deserialize",
+            Self::Redirect => "redirect",
+            Self::NoText => "no-text",
+            Self::Panic => "panic",
+            Self::Write => "write",
+        }
+    }
+
+    /// The kind of the first [ArticleError] in the chain of `error`, otherwise [ErrorKind::Write].
+    fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<ArticleError>())
+            .map(|e| e.kind)
+            .unwrap_or(Self::Write)
+    }
+}
+
+/// An article that couldn't be written, with the [ErrorKind] it is logged as.
+#[derive(Debug)]
+struct ArticleError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Display for ArticleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ArticleError {}
+
+/// Tab-separated log of articles that could not be processed, for `--error-log`.
+struct ErrorLog {
+    file: File,
+    path: PathBuf,
+}
+
+impl ErrorLog {
+    const HEADER: &str = "line\tbyte\tqid\turl\terror_kind\tmessage\n";
+
+    /// Open `path` for appending, writing the header if it is empty.
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut file = open_append(path, "error log")?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(Self::HEADER.as_bytes())?;
+        }
+
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+        })
+    }
+
+    fn write(
+        &mut self,
+        line: usize,
+        byte: usize,
+        page: Option<&Page>,
+        kind: ErrorKind,
+        error: &anyhow::Error,
+    ) -> anyhow::Result<()> {
+        let qid = page
+            .and_then(Page::wikidata)
+            .map(|qid| qid.to_string())
+            .unwrap_or_default();
+        let url = page.map(|p| p.url.as_str()).unwrap_or_default();
+        // Keep the error chain on a single row.
+        let message = format!("{:#}", error).replace(['\t', '\n'], " ");
+
+        let kind = kind.name();
+        let row = format!("{line}\t{byte}\t{qid}\t{url}\t{kind}\t{message}\n");
+        self.file
+            .write_all(row.as_bytes())
+            .with_context(|| format!("writing to error log {:?}", self.path))
+    }
+}
+
+/// Buffer size for reading dump files, large enough to hold multiple articles.
+const DUMP_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+/// Open the dump file at `path`, or stdin if there isn't one, and decompress it if necessary.
+fn open_dump(path: Option<&Path>) -> anyhow::Result<Box<dyn BufRead>> {
+    let dump: Box<dyn BufRead> = match path {
+        Some(path) => {
+            info!("Reading dump from {path:?}");
+            let file = File::open(path).with_context(|| format!("opening dump {:?}", path))?;
+            Box::new(BufReader::with_capacity(DUMP_BUFFER_SIZE, file))
+        }
+        None => {
+            info!("Reading dump from stdin");
+            Box::new(stdin().lock())
+        }
+    };
+
+    maybe_decompress(dump).context("detecting dump compression")
+}
+
+/// Wrap `reader` in a gzip decoder if its contents start with the gzip magic number.
+fn maybe_decompress(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        debug!("Decompressing gzipped dump");
+        let decoder = MultiGzDecoder::new(reader);
+        Ok(Box::new(BufReader::with_capacity(
+            DUMP_BUFFER_SIZE,
+            decoder,
+        )))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Log a line of the dump that could not be deserialized.
+///
+/// Returns the error unless `--skip-bad-lines` is set, in which case the line is counted in `matches`.
+fn bad_line(
+    args: &Args,
+    error_log: &mut Option<ErrorLog>,
+    matches: &mut Matches,
+    line: usize,
+    offset: usize,
+    e: anyhow::Error,
+) -> anyhow::Result<()> {
+    let e = e.context(format!("deserializing json on line {line} (byte {offset})"));
+    if let Some(log) = error_log {
+        log.write(line, offset, None, ErrorKind::Deserialize, &e)?;
+    }
+    if !args.skip_bad_lines {
+        return Err(e);
+    }
+    warn!("Skipping line: {:#}", e);
+    matches.skipped_lines += 1;
+    Ok(())
+}
+
+/// Write a line of the dump, adding a newline if it is missing at the end of the dump.
+fn write_line(writer: &mut impl Write, line: &[u8]) -> io::Result<()> {
+    writer.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Replace invalid UTF-8 sequences in `buffer` with `U+FFFD`, returning the position of the first one.
+///
+/// Broken exports can contain invalid bytes, which would otherwise fail deserialization of the whole line.
+fn replace_invalid_utf8(buffer: &mut Vec<u8>) -> Option<usize> {
+    let position = std::str::from_utf8(buffer).err()?.valid_up_to();
+    *buffer = String::from_utf8_lossy(buffer).into_owned().into_bytes();
+    Some(position)
+}
+
+/// Set when the process receives an interrupt signal, to stop [run_to] after the current line.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Extract the requested articles from `dump` to the output directory.
+///
+/// With `--dry-run` nothing is written, and the requested articles that were found are returned instead.
+fn run(args: &Args, dump: impl BufRead) -> anyhow::Result<Matches> {
+    run_to(args, dump, io::stdout().lock(), &INTERRUPTED)
+}
+
+/// Extract articles like [run], writing the `--passthrough` pages to `passthrough`.
+fn run_to(
+    args: &Args,
+    mut dump: impl BufRead,
+    mut passthrough: impl Write,
+    interrupted: &AtomicBool,
+) -> anyhow::Result<Matches> {
+    let wikipedia_titles = if let Some(path) = &args.wikipedia_urls {
+        info!("Loading article urls from {path:?}");
+        let urls = parse_wikipedia_file(path, args.strict_lang)?;
+        debug!("Parsed {} unique article urls", urls.len());
+        urls
+    } else {
+        Default::default()
+    };
+
+    let wikidata_ids = if let Some(path) = &args.wikidata_ids {
+        info!("Loading wikidata ids from {path:?}");
+        let ids = parse_wikidata_file(path)?;
+        debug!("Parsed {} unique wikidata ids", ids.len());
+        ids
+    } else {
+        Default::default()
+    };
+
+    let mut write_new_ids = args
+        .output_path(&args.write_new_ids)
+        .map(|p| open_append(p, "new ids file"))
+        .transpose()?;
+
+    let mut redirects_out = args
+        .output_path(&args.redirects_out)
+        .map(|p| open_append(p, "redirects file"))
+        .transpose()?;
+
+    let mut toc_out = args
+        .output_path(&args.toc_out)
+        .map(|p| open_append(p, "toc file"))
+        .transpose()?;
+
+    let mut thumbnail_out = args
+        .output_path(&args.thumbnail_out)
+        .map(|p| open_append(p, "thumbnail file"))
+        .transpose()?;
+
+    let mut description_out = args
+        .output_path(&args.description_out)
+        .map(|p| open_append(p, "description file"))
+        .transpose()?;
+
+    let mut classify_out = args
+        .output_path(&args.classify_out)
+        .map(|p| open_append(p, "kind file"))
+        .transpose()?;
+
+    let mut manifest = args
+        .output_path(&args.manifest)
+        .map(|p| open_append(p, "manifest"))
+        .transpose()?;
+
+    let mut error_log = args
+        .output_path(&args.error_log)
+        .map(ErrorLog::open)
+        .transpose()?;
+
+    let mut sections_to_remove = match &args.config {
+        Some(path) => load_config_file(path)?,
+        None => Default::default(),
+    };
+    if let Some(path) = &args.sections_file {
+        for (lang, titles) in load_sections_file(path)? {
+            sections_to_remove.entry(lang).or_default().extend(titles);
+        }
+    }
+
+    let simplify_options = SimplifyOptions {
+        elements: match &args.selectors {
+            Some(path) => ElementFilter::from_file(path)?,
+            None => ElementFilter::default(),
+        },
+        math_mode: args.math_mode,
+        table_mode: args.table_mode,
+        ruby_mode: args.ruby_mode,
+        sections_to_remove,
+        builtin_sections: args.config.is_none(),
+        max_header_level: args.max_header_level,
+        strip_bidi: args.strip_bidi,
+        keep_code: args.keep_code,
+        xhtml: args.xhtml,
+        image_alt_text: args.image_alt_text,
+        ..Default::default()
+    };
+
+    let mut output = if args.dry_run {
+        Output::Dir
+    } else {
+        Output::open(args)?
+    };
+
+    if !args.dry_run && matches!(output, Output::Dir) && !args.output_dir.is_dir() {
+        bail!("output dir {:?} does not exist", args.output_dir)
+    }
+
+    let mut matches = Matches {
+        tracker: MatchTracker::new(wikidata_ids, wikipedia_titles),
+        ..Default::default()
+    };
+
+    info!("Processing dump");
+
+    // The serde_json docs warn against using a reader directly, and it's slower than tar can decompress the dump.
+    // let stream = serde_json::Deserializer::from_reader(dump).into_iter::<Page>();
+    // Each line is read into a reusable buffer instead, which `simd-json` can parse in place.
+    let mut buffer = Vec::new();
+    let mut line = 0;
+    let mut byte = 0;
+    loop {
+        // Each article and output row is written with a single call, so stopping between lines doesn't leave partial ones.
+        if interrupted.load(Ordering::Relaxed) {
+            warn!("Interrupted after {line} lines ({byte} bytes) of the dump");
+            matches.interrupted = true;
+            break;
+        }
+
+        buffer.clear();
+        let len = dump
+            .read_until(b'\n', &mut buffer)
+            .context("reading dump")?;
+        if len == 0 {
+            break;
+        }
+        line += 1;
+        let offset = byte;
+        byte += len;
+
+        if let Some(position) = replace_invalid_utf8(&mut buffer) {
+            warn!(
+                "Replacing invalid UTF-8 on line {line} (byte {})",
+                offset + position
+            );
+        }
+
+        // Only the header is needed for matching, most pages are skipped without deserializing their html.
+        let header = match PageHeader::from_json_header(&buffer) {
+            Ok(header) => header,
+            Err(e) => {
+                bad_line(args, &mut error_log, &mut matches, line, offset, e)?;
+                continue;
+            }
+        };
+
+        if !args.languages.is_empty() && !args.languages.contains(&header.in_language.identifier) {
+            continue;
+        }
+
+        let qid = header.wikidata();
+
+        let is_wikidata_match = qid
+            .as_ref()
+            .map(|qid| matches.tracker.match_qid(qid))
+            .unwrap_or_default();
+
+        // When only QIDs are requested, like in the second pass with the ids from `--write-new-ids`,
+        // matching doesn't need to parse any titles.
+        let matching_titles = if matches.tracker.wikipedia_titles().is_empty() {
+            Default::default()
+        } else {
+            matches
+                .tracker
+                .match_titles(header.all_titles().filter_map(|r| {
+                    r.map(Some).unwrap_or_else(|e| {
+                        warn!("Could not parse title for {:?}: {:#}", &header.name, e);
+                        None
+                    })
+                }))
+        };
+
+        if !is_wikidata_match && matching_titles.is_empty() {
+            continue;
+        }
+
+        if args.dry_run {
+            continue;
+        }
+
+        // simd-json doesn't support borrowing the raw html, so the line is parsed again in place,
+        // and the original has to be kept to pass through.
+        #[cfg(feature = "simd-json")]
+        let original = (!args.passthrough.is_empty()).then(|| buffer.clone());
+        #[cfg(feature = "simd-json")]
+        let page = Page::from_json(&mut buffer);
+        #[cfg(not(feature = "simd-json"))]
+        let page = header.into_page();
+
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                bad_line(args, &mut error_log, &mut matches, line, offset, e)?;
+                continue;
+            }
+        };
+        #[cfg(feature = "simd-json")]
+        let original = original.as_deref().unwrap_or_default();
+        #[cfg(not(feature = "simd-json"))]
+        let original = &buffer;
+
+        // Parse the original html once for the checks and all of the outputs that need it.
+        let document = Html::parse_document(&page.article_body.html);
+
+        // Redirects are included as titles of the articles they point to.
+        if is_redirect(&document) {
+            debug!("Skipping redirect page {:?}", page.name);
+            if let Some(log) = error_log.as_mut().filter(|_| args.log_redirects) {
+                let e = anyhow!("skipped redirect page {}", describe(&page));
+                log.write(line, offset, Some(&page), ErrorKind::Redirect, &e)?;
+            }
+            if args.passthrough.contains(&Passthrough::Redirect) {
+                write_line(&mut passthrough, original).context("writing passthrough page")?;
+            }
+            continue;
+        }
+
+        let passthrough_disambiguation = args.passthrough.contains(&Passthrough::Disambiguation);
+        if (args.skip_disambiguation || passthrough_disambiguation) && is_disambiguation(&document)
+        {
+            if passthrough_disambiguation {
+                write_line(&mut passthrough, original).context("writing passthrough page")?;
+            }
+            if args.skip_disambiguation {
+                debug!("Skipping disambiguation page {:?}", page.name);
+                continue;
+            }
+        }
+
+        if let (Some(f), Some(qid)) = (&mut write_new_ids, &qid) {
+            if !is_wikidata_match && !matching_titles.is_empty() {
+                debug!("Writing new id {} for article {:?}", qid, page.name);
+                f.write_all(format!("{qid}\n").as_bytes())
+                    .with_context(|| {
+                        format!(
+                            "writing new id to file {:?}",
+                            args.write_new_ids.as_ref().unwrap()
+                        )
+                    })?;
+            }
+        }
+
+        if let Some(f) = &mut redirects_out {
+            write_redirects(f, &page, qid.as_ref()).with_context(|| {
+                format!(
+                    "writing redirects to file {:?}",
+                    args.redirects_out.as_ref().unwrap()
+                )
+            })?;
+        }
+
+        // Articles written to an archive or database don't have files for the manifest.
+        let result = match &mut output {
+            Output::Dir => write(args, &page, matching_titles, &simplify_options).map(Some),
+            Output::Tar(archive) => simplify_page(args, &page, &simplify_options)
+                .and_then(|html| archive.append(&page, &html, &matching_titles))
+                .map(|_| None),
+            #[cfg(feature = "sqlite")]
+            Output::Sqlite(db) => simplify_page(args, &page, &simplify_options)
+                .and_then(|html| db.insert(&page, &html, &matching_titles))
+                .map(|_| None),
+        };
+
+        let written = match result {
+            Ok(written) => written,
+            Err(e) => {
+                error!("Error writing article {:?}: {:#}", page.name, e);
+                if let Some(log) = &mut error_log {
+                    log.write(line, offset, Some(&page), ErrorKind::of(&e), &e)?;
+                }
+                continue;
+            }
+        };
+        if written.as_ref().map(|w| w.unchanged).unwrap_or_default() {
+            matches.unchanged += 1;
+        }
+
+        if let Some(f) = &mut description_out {
+            write_description(f, &page, qid.as_ref()).with_context(|| {
+                format!(
+                    "writing description to file {:?}",
+                    args.description_out.as_ref().unwrap()
+                )
+            })?;
+        }
+
+        if let (Some(f), Some(written)) = (&mut manifest, &written) {
+            write_manifest(f, &page, qid.as_ref(), written, args.link_mode).with_context(|| {
+                format!("writing manifest {:?}", args.manifest.as_ref().unwrap())
+            })?;
+        }
+
+        if let Some(f) = &mut toc_out {
+            write_toc(f, &page, qid.as_ref(), &document).with_context(|| {
+                format!("writing toc to file {:?}", args.toc_out.as_ref().unwrap())
+            })?;
+        }
+
+        if let Some(f) = &mut thumbnail_out {
+            write_thumbnail(f, &page, qid.as_ref(), &document).with_context(|| {
+                format!(
+                    "writing thumbnail to file {:?}",
+                    args.thumbnail_out.as_ref().unwrap()
+                )
+            })?;
+        }
+
+        if let Some(f) = &mut classify_out {
+            write_kind(f, &page, qid.as_ref(), &document).with_context(|| {
+                format!(
+                    "writing kind to file {:?}",
+                    args.classify_out.as_ref().unwrap()
+                )
+            })?;
+        }
+    }
+
+    passthrough.flush().context("writing passthrough lines")?;
+
+    output.finish(args)?;
+
+    if matches.skipped_lines > 0 {
+        warn!(
+            "Skipped {} lines that could not be deserialized",
+            matches.skipped_lines
+        );
+    }
+
+    if args.skip_unchanged {
+        info!("Skipped writing {} unchanged articles", matches.unchanged);
+    }
+
+    if args.dry_run {
+        matches.report();
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::HashSet, io::Read, str::FromStr};
+
+    fn page(name: &str, qid: Option<&str>, redirects: &[&str]) -> String {
+        json!({
+            "name": name,
+            "date_modified": "2023-06-01T00:00:00Z",
+            "in_language": { "identifier": "en" },
+            "url": format!("https://en.wikipedia.org/wiki/{}", name.replace(' ', "_")),
+            "main_entity": qid.map(|identifier| json!({ "identifier": identifier })),
+            "article_body": { "html": format!("<html><body><p>{name}</p></body></html>") },
+            "redirects": redirects
+                .iter()
+                .map(|r| json!({ "url": "", "name": r }))
+                .collect::<Vec<_>>(),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn log_level() {
+        use log::LevelFilter::*;
+        assert_eq!(Info, super::log_level(0, 0));
+        assert_eq!(Warn, super::log_level(1, 0));
+        assert_eq!(Error, super::log_level(2, 0));
+        assert_eq!(Error, super::log_level(5, 0));
+        assert_eq!(Debug, super::log_level(0, 1));
+        assert_eq!(Trace, super::log_level(0, 2));
+        assert_eq!(Trace, super::log_level(0, 5));
+        assert_eq!(Info, super::log_level(1, 1));
+    }
+
+    /// Log output that can be read back after being moved into a logger.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn log_format() {
+        for format in LogFormat::value_variants() {
+            let output = SharedBuffer::default();
+            let logger = logger(*format, log::LevelFilter::Info)
+                .target(env_logger::Target::Pipe(Box::new(output.clone())))
+                .is_test(true)
+                .build();
+
+            log::Log::log(
+                &logger,
+                &log::Record::builder()
+                    .level(log::Level::Warn)
+                    .target("om_wikiparser")
+                    .args(format_args!("Could not parse \"title\""))
+                    .build(),
+            );
+
+            let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+            match format {
+                LogFormat::Pretty => assert!(output.contains("Could not parse \"title\"")),
+                LogFormat::Logfmt => assert!(
+                    output.contains(
+                        r#"level=warn target=om_wikiparser msg="Could not parse \"title\"""#
+                    ),
+                    "{output:?}"
+                ),
+                LogFormat::Json => {
+                    let line: serde_json::Value = serde_json::from_str(&output).unwrap();
+                    assert_eq!("warn", line["level"]);
+                    assert_eq!("Could not parse \"title\"", line["msg"]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("output");
+        fs::create_dir(&output_dir).unwrap();
+
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ3\n").unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(
+            &urls,
+            "https://en.wikipedia.org/wiki/Page_B\n\
+             https://en.wikipedia.org/wiki/Redirect_to_D\n\
+             https://en.wikipedia.org/wiki/Missing\n",
+        )
+        .unwrap();
+        let new_ids = dir.path().join("new_ids.txt");
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            page("Page B", Some("Q2"), &[]),
+            page("Page C", Some("Q4"), &[]),
+            page("Page D", None, &["Redirect to D"]),
+        ]
+        .join("\n");
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--dry-run",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            "--write-new-ids",
+            new_ids.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+        ]);
+
+        let matches = run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            &HashSet::from([WikidataQid::from_str("Q1").unwrap()]),
+            matches.tracker.matched_qids()
+        );
+        assert_eq!(
+            &HashSet::from([
+                WikipediaTitleNorm::from_title("Page B", "en").unwrap(),
+                WikipediaTitleNorm::from_title("Redirect to D", "en").unwrap(),
+            ]),
+            matches.tracker.matched_titles()
+        );
+
+        assert!(
+            fs::read_dir(&output_dir).unwrap().next().is_none(),
+            "No articles should be written."
+        );
+        assert!(!new_ids.exists(), "New ids file should not be created.");
+    }
+
+    #[test]
+    fn qids_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ3\n").unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(&urls, "https://en.wikipedia.org/wiki/Missing\n").unwrap();
+
+        let dump = [
+            page("Page A", Some("Q1"), &["Redirect to A"]),
+            page("Page B", Some("Q2"), &[]),
+            page("Page C", None, &[]),
+            page("Page D", Some("Q3"), &[]),
+        ]
+        .join("\n");
+
+        let qids_only = Args::parse_from([
+            "om-wikiparser",
+            "--dry-run",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        let with_titles = Args::parse_from([
+            "om-wikiparser",
+            "--dry-run",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        let fast = run(&qids_only, dump.as_bytes()).unwrap();
+        let full = run(&with_titles, dump.as_bytes()).unwrap();
+
+        assert_eq!(2, fast.tracker.matched_qids().len());
+        assert_eq!(full.tracker.matched_qids(), fast.tracker.matched_qids());
+        assert!(fast.tracker.matched_titles().is_empty());
+        assert!(full.tracker.matched_titles().is_empty());
+    }
+
+    #[test]
+    fn languages() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\n").unwrap();
+
+        let in_lang = |lang: &str| {
+            page("Page A", Some("Q1"), &[])
+                .replace(r#""identifier":"en""#, &format!(r#""identifier":"{lang}""#))
+        };
+        let dump = [in_lang("en"), in_lang("de"), in_lang("fr")].join("\n");
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--languages",
+            "de,fr",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        run(&args, dump.as_bytes()).unwrap();
+
+        let mut written: Vec<_> = fs::read_dir(dir.path().join("wikidata/Q1"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        written.sort();
+        assert_eq!(vec!["de.html", "fr.html"], written);
+    }
+
+    #[test]
+    fn two_phase_matching() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ3\n").unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(
+            &urls,
+            "https://en.wikipedia.org/wiki/Page_B\nhttps://en.wikipedia.org/wiki/Redirect_to_E\n",
+        )
+        .unwrap();
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            page("Page B", Some("Q2"), &[]),
+            page("Page C", Some("Q3"), &["Redirect to C"]),
+            page("Page D", Some("Q4"), &[]),
+            page("Page E", None, &["Redirect to E"]),
+            page("Page F", None, &[]),
+        ];
+
+        // Match the fully deserialized pages.
+        let wikidata_ids = parse_wikidata_file(&ids).unwrap();
+        let wikipedia_titles = parse_wikipedia_file(&urls, false).unwrap();
+        let mut expected: Vec<_> = dump
+            .iter()
+            .map(|line| Page::from_json(&mut line.clone().into_bytes()).unwrap())
+            .filter(|page| {
+                page.wikidata()
+                    .map(|qid| wikidata_ids.contains(&qid))
+                    .unwrap_or_default()
+                    || page
+                        .all_titles()
+                        .any(|t| wikipedia_titles.contains(&t.unwrap()))
+            })
+            .map(|page| page.name)
+            .collect();
+        expected.sort();
+        assert_eq!(vec!["Page A", "Page B", "Page C", "Page E"], expected);
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            "--manifest",
+            dir.path().join("manifest.tsv").to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        run(&args, dump.join("\n").as_bytes()).unwrap();
+
+        let manifest = fs::read_to_string(dir.path().join("manifest.tsv")).unwrap();
+        let mut written: Vec<_> = manifest
+            .lines()
+            .filter(|l| l.starts_with("html\t"))
+            .map(|l| l.rsplit('\t').next().unwrap())
+            .collect();
+        written.sort();
+        assert_eq!(vec!["Page E", "Q1", "Q2", "Q3"], written);
+    }
+
+    #[test]
+    fn gzip_output() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\n").unwrap();
+
+        let dump = page("Page A", Some("Q1"), &[]);
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--gzip-output",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let article_dir = dir.path().join("wikidata/Q1");
+        assert!(!article_dir.join("en.html").exists());
+
+        let mut html = String::new();
+        GzDecoder::new(File::open(article_dir.join("en.html.gz")).unwrap())
+            .read_to_string(&mut html)
+            .unwrap();
+
+        assert_eq!(
+            om_wikiparser::html::simplify("<html><body><p>Page A</p></body></html>", "en"),
+            html
+        );
+    }
+
+    fn link_mode(mode: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(&urls, "https://en.wikipedia.org/wiki/Redirect_to_A\n").unwrap();
+
+        let dump = page("Page A", Some("Q1"), &["Redirect to A"]);
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--link-mode",
+            mode,
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let article = dir.path().join("wikidata/Q1/en.html");
+        let redirect_dir = dir.path().join("en.wikipedia.org/wiki/Redirect_to_A");
+        let redirect = redirect_dir.join("en.html");
+
+        assert!(!redirect_dir.is_symlink());
+        assert_eq!(
+            fs::read_to_string(article).unwrap(),
+            fs::read_to_string(redirect).unwrap()
+        );
+    }
+
+    #[test]
+    fn interrupted() {
+        /// Returns one line of the dump for each read, and sets `interrupted` when reading line `interrupt_at`.
+        struct InterruptingReader<'a> {
+            lines: Vec<String>,
+            read: usize,
+            interrupt_at: usize,
+            interrupted: &'a AtomicBool,
+        }
+
+        impl Read for InterruptingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let Some(line) = self.lines.get(self.read) else {
+                    return Ok(0);
+                };
+                self.read += 1;
+                if self.read == self.interrupt_at {
+                    self.interrupted.store(true, Ordering::Relaxed);
+                }
+                let line = format!("{line}\n");
+                assert!(line.len() <= buf.len());
+                buf[..line.len()].copy_from_slice(line.as_bytes());
+                Ok(line.len())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\nQ3\n").unwrap();
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        let interrupted = AtomicBool::new(false);
+        let dump = InterruptingReader {
+            lines: vec![
+                page("Page A", Some("Q1"), &[]),
+                page("Page B", Some("Q2"), &[]),
+                page("Page C", Some("Q3"), &[]),
+            ],
+            read: 0,
+            interrupt_at: 2,
+            interrupted: &interrupted,
+        };
+        let matches = run_to(&args, BufReader::new(dump), io::sink(), &interrupted).unwrap();
+
+        assert!(matches.interrupted);
+        // The article being written when interrupted is finished.
+        for qid in ["Q1", "Q2"] {
+            let html = fs::read_to_string(dir.path().join("wikidata").join(qid).join("en.html"));
+            assert!(
+                html.unwrap().contains("</html>"),
+                "{qid} should be complete"
+            );
+        }
+        assert!(!dir.path().join("wikidata/Q3").exists());
+    }
+
+    #[test]
+    fn skip_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+
+        for gzip in [false, true] {
+            let mut args = vec![
+                "om-wikiparser",
+                "--skip-unchanged",
+                "--wikidata-ids",
+                ids.to_str().unwrap(),
+                dir.path().to_str().unwrap(),
+            ];
+            if gzip {
+                args.push("--gzip-output");
+            }
+            let args = Args::parse_from(args);
+
+            let dump = [
+                page("Page A", Some("Q1"), &[]),
+                page("Page B", Some("Q2"), &[]),
+            ]
+            .join("\n");
+            assert_eq!(0, run(&args, dump.as_bytes()).unwrap().unchanged);
+            assert_eq!(2, run(&args, dump.as_bytes()).unwrap().unchanged);
+
+            let dump = [
+                page("Page A", Some("Q1"), &[]),
+                page("Page B", Some("Q2"), &[]).replace("<p>Page B</p>", "<p>Page B, edited</p>"),
+            ]
+            .join("\n");
+            assert_eq!(1, run(&args, dump.as_bytes()).unwrap().unchanged);
+        }
+        assert!(fs::read_to_string(dir.path().join("wikidata/Q2/en.html"))
+            .unwrap()
+            .contains("edited"));
+    }
+
+    #[test]
+    fn completions() {
+        let args = Args::parse_from(["om-wikiparser", "--completions", "bash"]);
+        assert_eq!(Some(Shell::Bash), args.completions);
+        assert!(Args::try_parse_from(["om-wikiparser"]).is_err());
+        assert!(Args::try_parse_from(["om-wikiparser", "--completions", "bash", "out"]).is_err());
+
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            clap_complete::generate(*shell, &mut Args::command(), "om-wikiparser", &mut script);
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("wikidata-ids"), "{shell}: {script}");
+        }
+    }
+
+    #[test]
+    fn tar_out_dump_file() {
+        let parse = |args: &[&str]| {
+            Args::try_parse_args(
+                ["om-wikiparser", "--wikidata-ids", "ids.txt"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+
+        let args = parse(&["--tar-out", "a.tar", "dump.json"]).unwrap();
+        assert_eq!(Some(Path::new("a.tar")), args.tar_out.as_deref());
+        assert_eq!(Some(Path::new("dump.json")), args.dump_file.as_deref());
+
+        let args = parse(&["--tar-out", "a.tar"]).unwrap();
+        assert_eq!(None, args.dump_file);
+
+        assert!(parse(&["--tar-out", "a.tar", "out", "dump.json"]).is_err());
+
+        let args = parse(&["out", "dump.json"]).unwrap();
+        assert_eq!(Path::new("out"), args.output_dir);
+        assert_eq!(Some(Path::new("dump.json")), args.dump_file.as_deref());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_dump_file() {
+        let args = Args::try_parse_args([
+            "om-wikiparser",
+            "--wikidata-ids",
+            "ids.txt",
+            "--sqlite",
+            "out.db",
+            "dump.json",
+        ])
+        .unwrap();
+        assert_eq!(Some(Path::new("out.db")), args.sqlite.as_deref());
+        assert_eq!(Some(Path::new("dump.json")), args.dump_file.as_deref());
+    }
+
+    #[test]
+    fn replace_dangling_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(&urls, "https://en.wikipedia.org/wiki/Redirect_to_A\n").unwrap();
+
+        let redirect_dir = dir.path().join("en.wikipedia.org/wiki/Redirect_to_A");
+        fs::create_dir_all(redirect_dir.parent().unwrap()).unwrap();
+        symlink_dir(dir.path().join("wikidata/Q404"), &redirect_dir).unwrap();
+
+        let dump = page("Page A", Some("Q1"), &["Redirect to A"]);
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            dir.path().join("wikidata/Q1"),
+            fs::read_link(&redirect_dir).unwrap()
+        );
+        assert!(redirect_dir.join("en.html").exists());
+    }
+
+    #[test]
+    fn repair_broken_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let wiki = dir.path().join("en.wikipedia.org/wiki");
+        let article = dir.path().join("wikidata/Q1");
+        let empty = dir.path().join("wikidata/Q2");
+        fs::create_dir_all(&wiki).unwrap();
+        fs::create_dir_all(&article).unwrap();
+        fs::create_dir_all(&empty).unwrap();
+        fs::write(article.join("en.html"), "<p>Page A</p>").unwrap();
+
+        symlink_dir(&article, wiki.join("Page_A")).unwrap();
+        symlink_dir(&empty, wiki.join("Empty")).unwrap();
+        symlink_dir(dir.path().join("wikidata/Q404"), wiki.join("Dangling")).unwrap();
+        // Titles with `/` are nested.
+        fs::create_dir_all(wiki.join("AC")).unwrap();
+        symlink_dir(dir.path().join("wikidata/Q405"), wiki.join("AC/DC")).unwrap();
+        // Links into an output directory that was moved here.
+        symlink_dir(dir.path().join("old/wikidata/Q1"), wiki.join("Moved")).unwrap();
+
+        assert_eq!((1, 3), super::repair_broken_links(dir.path()).unwrap());
+        assert!(wiki.join("Page_A/en.html").exists());
+        assert_eq!(
+            article.canonicalize().unwrap(),
+            wiki.join("Moved").canonicalize().unwrap()
+        );
+        assert_eq!(
+            "<p>Page A</p>",
+            fs::read_to_string(wiki.join("Moved/en.html")).unwrap()
+        );
+        for removed in ["Empty", "Dangling", "AC/DC"] {
+            assert!(
+                !wiki.join(removed).is_symlink(),
+                "{removed} should be removed"
+            );
+        }
+        assert!(wiki.join("AC").is_dir());
+
+        assert_eq!((0, 0), super::repair_broken_links(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn link_mode_copy() {
+        link_mode("copy");
+    }
+
+    #[test]
+    fn link_mode_hardlink() {
+        link_mode("hardlink");
+    }
+
+    #[test]
+    fn layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(
+            &urls,
+            "https://en.wikipedia.org/wiki/Redirect_to_A\nhttps://en.wikipedia.org/wiki/St._Page_B\n",
+        )
+        .unwrap();
+        let dump = [
+            page("Page A", Some("Q1"), &["Redirect to A"]),
+            page("St. Page B", None, &[]),
+        ]
+        .join("\n");
+
+        let output = |layout: &str| {
+            let output_dir = dir.path().join(layout);
+            fs::create_dir(&output_dir).unwrap();
+            let args = Args::parse_from([
+                "om-wikiparser",
+                "--layout",
+                layout,
+                "--wikipedia-urls",
+                urls.to_str().unwrap(),
+                output_dir.to_str().unwrap(),
+            ]);
+            run(&args, dump.as_bytes()).unwrap();
+            output_dir
+        };
+        let read =
+            |path: PathBuf| fs::read_to_string(&path).unwrap_or_else(|e| panic!("{path:?}: {e}"));
+
+        let generator = output("generator");
+        assert!(read(generator.join("wikidata/Q1/en.html")).contains("Page A"));
+        assert!(generator
+            .join("en.wikipedia.org/wiki/Redirect_to_A")
+            .is_symlink());
+        assert!(
+            read(generator.join("en.wikipedia.org/wiki/St._Page_B/en.html")).contains("Page B")
+        );
+
+        // The article without a QID isn't written.
+        let flat_qid = output("flat-qid");
+        assert!(read(flat_qid.join("Q1.en.html")).contains("Page A"));
+        assert_eq!(1, fs::read_dir(&flat_qid).unwrap().count());
+
+        let by_lang = output("by-lang");
+        assert!(read(by_lang.join("en/Page_A.html")).contains("Page A"));
+        assert!(by_lang.join("en/Redirect_to_A.html").is_symlink());
+        assert!(read(by_lang.join("en/Redirect_to_A.html")).contains("Page A"));
+        assert!(read(by_lang.join("en/St._Page_B.html")).contains("Page B"));
+        assert_eq!(3, fs::read_dir(by_lang.join("en")).unwrap().count());
+    }
+
+    #[test]
+    fn redirects_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\n").unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(&urls, "https://en.wikipedia.org/wiki/Page_D\n").unwrap();
+        let redirects = dir.path().join("redirects.tsv");
+
+        let dump = [
+            page(
+                "Page A",
+                Some("Q1"),
+                &["Redirect 1 to A", "Redirect 2 to A"],
+            ),
+            page("Page B", Some("Q2"), &["Redirect to B"]),
+            page("Page D", None, &["Redirect to D"]),
+        ]
+        .join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            "--redirects-out",
+            redirects.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            "en\tRedirect 1 to A\tQ1\n\
+             en\tRedirect 2 to A\tQ1\n\
+             en\tRedirect to D\tPage D\n",
+            fs::read_to_string(redirects).unwrap()
+        );
+    }
+
+    #[test]
+    fn self_redirects() {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(
+            &urls,
+            "https://en.wikipedia.org/wiki/Page_D\n\
+             https://en.wikipedia.org/wiki/Page_E\n\
+             https://en.wikipedia.org/wiki/Page_E/Part\n",
+        )
+        .unwrap();
+
+        let dump = [
+            page("Page D", None, &["Page D"]),
+            page("Page E", None, &["Page E/Part"]),
+        ]
+        .join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let wiki = dir.path().join("en.wikipedia.org/wiki");
+        for name in ["Page_D", "Page_E"] {
+            let article_dir = wiki.join(name);
+            assert!(!article_dir.is_symlink(), "{name} should not be a link");
+            assert!(article_dir.join("en.html").is_file());
+        }
+        assert!(!wiki.join("Page_E/Part").exists());
+    }
+
+    #[test]
+    fn manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = dir.path().join("urls.txt");
+        fs::write(&urls, "https://en.wikipedia.org/wiki/Page_A\n").unwrap();
+        let manifest = dir.path().join("manifest.tsv");
+
+        let dump = page("Page A", Some("Q1"), &[]);
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikipedia-urls",
+            urls.to_str().unwrap(),
+            "--manifest",
+            manifest.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let base = dir.path().display();
+        assert_eq!(
+            format!(
+                "html\t{base}/wikidata/Q1/en.html\tQ1\n\
+                 symlink\t{base}/en.wikipedia.org/wiki/Page_A\t{base}/wikidata/Q1\n"
+            ),
+            fs::read_to_string(manifest).unwrap()
+        );
+    }
+
+    #[test]
+    fn toc_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\n").unwrap();
+        let toc = dir.path().join("toc.json");
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            page("Page B", Some("Q2"), &[]),
+        ]
+        .join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--toc-out",
+            toc.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(toc)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            vec![json!({
+                "url": "https://en.wikipedia.org/wiki/Page_A",
+                "qid": "Q1",
+                "toc": [],
+            })],
+            lines
+        );
+    }
+
+    #[test]
+    fn classify_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+        let kinds = dir.path().join("kinds.tsv");
+
+        let disambiguation = page("Page B", Some("Q2"), &[]).replace(
+            "<html>",
+            r#"<html><head><meta property=\"mw:PageProp/disambiguation\"/></head>"#,
+        );
+        let dump = [page("Page A", Some("Q1"), &[]), disambiguation].join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--classify-out",
+            kinds.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            "en\tQ1\tstub\nen\tQ2\tdisambiguation\n",
+            fs::read_to_string(kinds).unwrap()
+        );
+    }
+
+    #[test]
+    fn redirect_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+        let error_log = dir.path().join("errors.tsv");
+
+        let redirect = page("Page B", Some("Q2"), &[]).replace(
+            "<body>",
+            r#"<body><link rel=\"mw:PageProp/redirect\" href=\"./Page_A\"/>"#,
+        );
+        let dump = [page("Page A", Some("Q1"), &[]), redirect].join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--error-log",
+            error_log.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        assert!(dir.path().join("wikidata/Q1/en.html").is_file());
+        assert!(!dir.path().join("wikidata/Q2").exists());
+        assert_eq!(ErrorLog::HEADER, fs::read_to_string(&error_log).unwrap());
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--error-log",
+            error_log.to_str().unwrap(),
+            "--log-redirects",
+            dir.path().to_str().unwrap(),
+        ]);
+        run(&args, dump.as_bytes()).unwrap();
+
+        let log = fs::read_to_string(error_log).unwrap();
+        let rows: Vec<Vec<_>> = log.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(2, rows.len(), "Expected header and one row: {log:?}");
+        assert_eq!(
+            ["2", "Q2", "redirect"],
+            [rows[1][0], rows[1][2], rows[1][4]]
+        );
+    }
+
+    #[test]
+    fn passthrough() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\nQ3\n").unwrap();
+
+        let redirect = page("Page B", Some("Q2"), &[]).replace(
+            "<body>",
+            r#"<body><link rel=\"mw:PageProp/redirect\" href=\"./Page_A\"/>"#,
+        );
+        let disambiguation = page("Page C", Some("Q3"), &[]).replace(
+            "<html>",
+            r#"<html><head><meta property=\"mw:PageProp/disambiguation\"/></head>"#,
+        );
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            redirect.clone(),
+            disambiguation.clone(),
+        ]
+        .join("\n");
+
+        let passthrough = |values: &str| {
+            let args = Args::parse_from([
+                "om-wikiparser",
+                "--skip-disambiguation",
+                "--passthrough",
+                values,
+                "--wikidata-ids",
+                ids.to_str().unwrap(),
+                dir.path().to_str().unwrap(),
+            ]);
+            let mut out = Vec::new();
+            run_to(&args, dump.as_bytes(), &mut out, &AtomicBool::new(false)).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(format!("{redirect}\n"), passthrough("redirect"));
+        assert_eq!(format!("{disambiguation}\n"), passthrough("disambiguation"));
+        assert_eq!(
+            format!("{redirect}\n{disambiguation}\n"),
+            passthrough("redirect,disambiguation")
+        );
+        assert!(!dir.path().join("wikidata/Q2").exists());
+        assert!(!dir.path().join("wikidata/Q3").exists());
+    }
+
+    #[test]
+    fn description_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+        let descriptions = dir.path().join("descriptions.tsv");
+
+        let described =
+            page("Page A", Some("Q1"), &[]).replacen('{', r#"{"description":"First\tpage","#, 1);
+        let dump = [described, page("Page B", Some("Q2"), &[])].join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--description-out",
+            descriptions.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            "en\tQ1\tFirst page\n",
+            fs::read_to_string(descriptions).unwrap()
+        );
+    }
+
+    #[test]
+    fn error_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\n").unwrap();
+        let error_log = dir.path().join("errors.tsv");
+        // Block the article directory from being created.
+        fs::write(dir.path().join("wikidata"), "").unwrap();
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            page("Page B", Some("Q2"), &[]),
+            "{\"name\": ".to_string(),
+        ]
+        .join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--error-log",
+            error_log.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        assert!(run(&args, dump.as_bytes()).is_err());
+
+        let log = fs::read_to_string(error_log).unwrap();
+        let rows: Vec<Vec<_>> = log.lines().map(|l| l.split('\t').collect()).collect();
+
+        assert_eq!(3, rows.len(), "Expected header and two rows: {log:?}");
+        assert_eq!(
+            [
+                "1",
+                "0",
+                "Q1",
+                "https://en.wikipedia.org/wiki/Page_A",
+                "write"
+            ],
+            rows[1][..5]
+        );
+        assert_eq!(
+            ["3", "", "", "deserialize"],
+            [rows[2][0], rows[2][2], rows[2][3], rows[2][4]]
+        );
+    }
+
+    #[test]
+    fn error_log_no_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+        let error_log = dir.path().join("errors.tsv");
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]).replace("<p>Page A</p>", "<p> </p>"),
+            page("Page B", Some("Q2"), &[]),
+        ]
+        .join("\n");
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            "--error-log",
+            error_log.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+
+        run(&args, dump.as_bytes()).unwrap();
+
+        let log = fs::read_to_string(error_log).unwrap();
+        let rows: Vec<Vec<_>> = log.lines().map(|l| l.split('\t').collect()).collect();
+
+        assert_eq!(2, rows.len(), "Expected header and one row: {log:?}");
+        assert_eq!(
+            [
+                "1",
+                "0",
+                "Q1",
+                "https://en.wikipedia.org/wiki/Page_A",
+                "no-text"
+            ],
+            rows[1][..5]
+        );
+        assert!(!dir.path().join("wikidata/Q1/en.html").exists());
+        assert!(dir.path().join("wikidata/Q2/en.html").exists());
+    }
+
+    #[test]
+    fn skip_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            "{\"name\": \"Trunc".to_string(),
+            page("Page B", Some("Q2"), &[]),
+        ]
+        .join("\n");
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        assert!(run(&args, dump.as_bytes()).is_err());
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--skip-bad-lines",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        let matches = run(&args, dump.as_bytes()).unwrap();
+
+        assert_eq!(1, matches.skipped_lines);
+        for qid in ["Q1", "Q2"] {
+            assert!(dir
+                .path()
+                .join("wikidata")
+                .join(qid)
+                .join("en.html")
+                .is_file());
+        }
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut buffer = b"valid".to_vec();
+        assert_eq!(None, replace_invalid_utf8(&mut buffer));
+        assert_eq!(b"valid", buffer.as_slice());
+
+        let mut buffer = b"in\xffvalid".to_vec();
+        assert_eq!(Some(2), replace_invalid_utf8(&mut buffer));
+        assert_eq!("in\u{FFFD}valid".as_bytes(), buffer.as_slice());
+
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+
+        let mut dump = page("Page A", Some("Q1"), &[]).replace("Page A</p>", "Page \u{1F600}A</p>");
+        dump.push('\n');
+        dump.push_str(&page("Page B", Some("Q2"), &[]));
+        // Truncate the emoji in the html of the first article.
+        let mut dump = dump.into_bytes();
+        let emoji = dump
+            .windows(4)
+            .position(|w| w == "\u{1F600}".as_bytes())
+            .unwrap();
+        dump.drain(emoji + 1..emoji + 4);
+
+        let args = Args::parse_from([
+            "om-wikiparser",
+            "--wikidata-ids",
+            ids.to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]);
+        run(&args, dump.as_slice()).unwrap();
+
+        let html = fs::read_to_string(dir.path().join("wikidata/Q1/en.html")).unwrap();
+        assert!(html.contains("Page \u{FFFD}A"), "{html}");
+        assert!(dir.path().join("wikidata/Q2/en.html").is_file());
+    }
+
+    #[test]
+    fn catch_panic() {
+        assert_eq!(1, super::catch_panic(|| 1).unwrap());
+
+        let page: Page = serde_json::from_str(&page("Page A", Some("Q1"), &[])).unwrap();
+        let e = super::catch_panic(|| panic!("bad html in {}", page.name))
+            .with_context(|| format!("simplifying article {}", describe(&page)))
+            .unwrap_err();
+        assert_eq!(
+            "simplifying article \"Page A\" (Q1, https://en.wikipedia.org/wiki/Page_A): panicked: bad html in Page A",
+            format!("{e:#}")
+        );
+
+        let e = super::catch_panic(|| panic!("static message")).unwrap_err();
+        assert_eq!("panicked: static message", e.to_string());
+    }
+
+    #[test]
+    fn dump_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "Q1\nQ2\n").unwrap();
+
+        let dump = [
+            page("Page A", Some("Q1"), &[]),
+            page("Page B", Some("Q2"), &[]),
+        ]
+        .join("\n");
+
+        let plain = dir.path().join("dump.json");
+        fs::write(&plain, &dump).unwrap();
+
+        let gzipped = dir.path().join("dump.json.gz");
+        let mut encoder = GzEncoder::new(File::create(&gzipped).unwrap(), Compression::default());
+        encoder.write_all(dump.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        for path in [plain, gzipped] {
+            let args = Args::parse_from([
+                "om-wikiparser",
+                "--dry-run",
+                "--wikidata-ids",
+                ids.to_str().unwrap(),
+                dir.path().to_str().unwrap(),
+                path.to_str().unwrap(),
+            ]);
+
+            let dump = open_dump(args.dump_file.as_deref()).unwrap();
+            let matches = run(&args, dump).unwrap();
+
+            assert_eq!(
+                2,
+                matches.tracker.matched_qids().len(),
+                "Both pages in {path:?} should match."
+            );
+        }
+    }
 }
@@ -0,0 +1,183 @@
+//! Write extracted articles to a single SQLite database, instead of a directory tree of files and links.
+use std::path::Path;
+
+use anyhow::Context;
+use rusqlite::{params, Connection};
+
+use crate::wm::{Page, WikipediaTitleNorm};
+
+/// Number of rows to insert in each transaction.
+///
+/// Committing after every article is much slower than the directory output.
+pub const BATCH_SIZE: usize = 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS articles (
+    -- The QID of the article, or its title if it doesn't have one.
+    key TEXT NOT NULL,
+    lang TEXT NOT NULL,
+    url TEXT NOT NULL,
+    date_modified TEXT NOT NULL,
+    html TEXT NOT NULL,
+    PRIMARY KEY (key, lang)
+);
+CREATE TABLE IF NOT EXISTS redirects (
+    lang TEXT NOT NULL,
+    title TEXT NOT NULL,
+    key TEXT NOT NULL,
+    PRIMARY KEY (lang, title)
+);
+";
+
+/// A database of simplified articles, with the titles that point to them.
+///
+/// Rows are inserted in batches of [BATCH_SIZE], call [ArticleDb::finish] to commit the last one.
+pub struct ArticleDb {
+    conn: Connection,
+    pending: usize,
+}
+
+impl ArticleDb {
+    /// Open or create the database at `path`, and its tables.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let conn =
+            Connection::open(path).with_context(|| format!("opening database {:?}", path))?;
+        Self::init(conn).with_context(|| format!("creating tables in database {:?}", path))
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        conn.execute_batch("BEGIN")?;
+        Ok(Self { conn, pending: 0 })
+    }
+
+    /// Insert the simplified `html` of `page`, replacing an existing row for the same article and language.
+    ///
+    /// `titles` are added to the `redirects` table as the wikipedia titles of the article.
+    pub fn insert(
+        &mut self,
+        page: &Page,
+        html: &str,
+        titles: &[WikipediaTitleNorm],
+    ) -> anyhow::Result<()> {
+        let key = page
+            .wikidata()
+            .map(|qid| qid.to_string())
+            .unwrap_or_else(|| page.name.clone());
+
+        self.conn
+            .prepare_cached(
+                "INSERT OR REPLACE INTO articles (key, lang, url, date_modified, html) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?
+            .execute(params![
+                key,
+                page.in_language.identifier,
+                page.url,
+                page.date_modified,
+                html
+            ])
+            .context("inserting article")?;
+
+        let mut insert_redirect = self.conn.prepare_cached(
+            "INSERT OR REPLACE INTO redirects (lang, title, key) VALUES (?1, ?2, ?3)",
+        )?;
+        for title in titles {
+            insert_redirect
+                .execute(params![title.lang(), title.name(), key])
+                .context("inserting redirect")?;
+        }
+        drop(insert_redirect);
+
+        self.pending += 1 + titles.len();
+        if self.pending >= BATCH_SIZE {
+            self.conn
+                .execute_batch("COMMIT; BEGIN")
+                .context("committing batch")?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    /// Commit the rows that were inserted since the last batch.
+    pub fn finish(self) -> anyhow::Result<()> {
+        self.conn
+            .execute_batch("COMMIT")
+            .context("committing last batch")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn page(name: &str, qid: Option<&str>) -> Page {
+        let mut json = serde_json::json!({
+            "name": name,
+            "date_modified": "2023-06-01T00:00:00Z",
+            "in_language": { "identifier": "en" },
+            "url": format!("https://en.wikipedia.org/wiki/{}", name.replace(' ', "_")),
+            "main_entity": qid.map(|identifier| serde_json::json!({ "identifier": identifier })),
+            "article_body": { "html": "" },
+            "redirects": [],
+        })
+        .to_string()
+        .into_bytes();
+        Page::from_json(&mut json).unwrap()
+    }
+
+    #[test]
+    fn insert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("articles.db");
+        let title = |name: &str| WikipediaTitleNorm::from_title(name, "en").unwrap();
+
+        let mut db = ArticleDb::open(&path).unwrap();
+        db.insert(
+            &page("Berlin", Some("Q64")),
+            "<p>Berlin</p>",
+            &[title("Berlin"), title("Berlin, Germany")],
+        )
+        .unwrap();
+        db.insert(&page("Paris", None), "<p>Paris</p>", &[title("Paris")])
+            .unwrap();
+        db.finish().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let articles: Vec<(String, String, String, String)> = conn
+            .prepare("SELECT key, lang, url, html FROM articles ORDER BY key")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                (
+                    "Paris".to_owned(),
+                    "en".to_owned(),
+                    "https://en.wikipedia.org/wiki/Paris".to_owned(),
+                    "<p>Paris</p>".to_owned()
+                ),
+                (
+                    "Q64".to_owned(),
+                    "en".to_owned(),
+                    "https://en.wikipedia.org/wiki/Berlin".to_owned(),
+                    "<p>Berlin</p>".to_owned()
+                ),
+            ],
+            articles
+        );
+
+        let html: String = conn
+            .query_row(
+                "SELECT html FROM articles JOIN redirects USING (key, lang) WHERE title = ?1",
+                ["Berlin,_Germany"],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!("<p>Berlin</p>", html);
+    }
+}
@@ -0,0 +1,50 @@
+//! WebAssembly bindings for previewing article simplification in the browser.
+//!
+//! Build with `cargo rustc --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`, see the README.
+//! Only the string-based html functions are exposed, nothing that touches the filesystem.
+use wasm_bindgen::prelude::*;
+
+use crate::html;
+
+/// Log panics to the browser console, instead of failing with an opaque `unreachable` error.
+#[wasm_bindgen(start)]
+pub fn start() {
+    console_error_panic_hook::set_once();
+}
+
+/// Simplify the html of an article in `lang`, see [html::simplify].
+#[wasm_bindgen]
+pub fn simplify_html(input: &str, lang: &str) -> String {
+    html::simplify(input, lang)
+}
+
+/// Detect the language of an article, see [html::detect_lang].
+#[wasm_bindgen]
+pub fn detect_lang(input: &str) -> Option<String> {
+    html::detect_lang(input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn simplify_html() {
+        let input = r#"<html><head><base href="//de.wikipedia.org/wiki/"></head><body>
+            <p>Ein <a href="./Artikel">Artikel</a>.</p>
+            <h2>Literatur</h2><p>Removed.</p>
+        </body></html>"#;
+
+        let lang = detect_lang(input).unwrap();
+        assert_eq!("de", lang);
+
+        let output = super::simplify_html(input, &lang);
+        assert!(output.contains("<p>Ein Artikel.</p>"));
+        assert!(!output.contains("Removed."));
+
+        assert_eq!(
+            None,
+            detect_lang("<html><head></head><body><p>No base</p></body></html>")
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Known Wikipedia language codes.
+//!
+//! Titles are stored with the code of the edition they are in, which is the subdomain of its url.
+//! Some editions are for variants of a language, and have their own subdomain:
+//! - `zh-yue` (Cantonese), `zh-classical` (Classical Chinese), and `zh-min-nan` (Min Nan)
+//! - `be-tarask` (Belarusian in the Taraškievica orthography), formerly `be-x-old`
+//! - `simple` (Simple English)
+//!
+//! Script and region variants, like `zh-hans`, `sr-el`, or `en-us`,
+//! are displayed by the edition of their language and are normalized to it, e.g. `zh` or `sr`.
+
+/// Subdomains of the Wikipedia language editions, sorted.
+///
+/// See <https://meta.wikimedia.org/wiki/List_of_Wikipedias>.
+const WIKIPEDIA_LANGS: &[&str] = &[
+    "aa",
+    "ab",
+    "ace",
+    "ady",
+    "af",
+    "ak",
+    "als",
+    "alt",
+    "am",
+    "ami",
+    "an",
+    "ang",
+    "ann",
+    "anp",
+    "ar",
+    "arc",
+    "ary",
+    "arz",
+    "as",
+    "ast",
+    "atj",
+    "av",
+    "avk",
+    "awa",
+    "ay",
+    "az",
+    "azb",
+    "ba",
+    "ban",
+    "bar",
+    "bat-smg",
+    "bcl",
+    "be",
+    "be-tarask",
+    "bg",
+    "bh",
+    "bi",
+    "bjn",
+    "blk",
+    "bm",
+    "bn",
+    "bo",
+    "bpy",
+    "br",
+    "bs",
+    "bug",
+    "bxr",
+    "ca",
+    "cbk-zam",
+    "cdo",
+    "ce",
+    "ceb",
+    "ch",
+    "cho",
+    "chr",
+    "chy",
+    "ckb",
+    "co",
+    "cr",
+    "crh",
+    "cs",
+    "csb",
+    "cu",
+    "cv",
+    "cy",
+    "da",
+    "dag",
+    "de",
+    "din",
+    "diq",
+    "dsb",
+    "dty",
+    "dv",
+    "dz",
+    "ee",
+    "el",
+    "eml",
+    "en",
+    "eo",
+    "es",
+    "et",
+    "eu",
+    "ext",
+    "fa",
+    "fat",
+    "ff",
+    "fi",
+    "fiu-vro",
+    "fj",
+    "fo",
+    "fon",
+    "fr",
+    "frp",
+    "frr",
+    "fur",
+    "fy",
+    "ga",
+    "gag",
+    "gan",
+    "gcr",
+    "gd",
+    "gl",
+    "glk",
+    "gn",
+    "gom",
+    "gor",
+    "got",
+    "gpe",
+    "gu",
+    "guc",
+    "gur",
+    "guw",
+    "gv",
+    "ha",
+    "hak",
+    "haw",
+    "he",
+    "hi",
+    "hif",
+    "ho",
+    "hr",
+    "hsb",
+    "ht",
+    "hu",
+    "hy",
+    "hyw",
+    "hz",
+    "ia",
+    "id",
+    "ie",
+    "ig",
+    "igl",
+    "ii",
+    "ik",
+    "ilo",
+    "inh",
+    "io",
+    "is",
+    "it",
+    "iu",
+    "ja",
+    "jam",
+    "jbo",
+    "jv",
+    "ka",
+    "kaa",
+    "kab",
+    "kbd",
+    "kbp",
+    "kcg",
+    "kg",
+    "ki",
+    "kj",
+    "kk",
+    "kl",
+    "km",
+    "kn",
+    "ko",
+    "koi",
+    "kr",
+    "krc",
+    "ks",
+    "ksh",
+    "ku",
+    "kus",
+    "kv",
+    "kw",
+    "ky",
+    "la",
+    "lad",
+    "lb",
+    "lbe",
+    "lez",
+    "lfn",
+    "lg",
+    "li",
+    "lij",
+    "lld",
+    "lmo",
+    "ln",
+    "lo",
+    "lrc",
+    "lt",
+    "ltg",
+    "lv",
+    "mad",
+    "mai",
+    "map-bms",
+    "mdf",
+    "mg",
+    "mh",
+    "mhr",
+    "mi",
+    "min",
+    "mk",
+    "ml",
+    "mn",
+    "mni",
+    "mnw",
+    "mo",
+    "mr",
+    "mrj",
+    "ms",
+    "mt",
+    "mus",
+    "mwl",
+    "my",
+    "myv",
+    "mzn",
+    "na",
+    "nah",
+    "nap",
+    "nds",
+    "nds-nl",
+    "ne",
+    "new",
+    "ng",
+    "nia",
+    "nl",
+    "nn",
+    "no",
+    "nov",
+    "nqo",
+    "nr",
+    "nrm",
+    "nso",
+    "nv",
+    "ny",
+    "oc",
+    "olo",
+    "om",
+    "or",
+    "os",
+    "pa",
+    "pag",
+    "pam",
+    "pap",
+    "pcd",
+    "pcm",
+    "pdc",
+    "pfl",
+    "pi",
+    "pih",
+    "pl",
+    "pms",
+    "pnb",
+    "pnt",
+    "ps",
+    "pt",
+    "pwn",
+    "qu",
+    "rm",
+    "rmy",
+    "rn",
+    "ro",
+    "roa-rup",
+    "roa-tara",
+    "ru",
+    "rue",
+    "rw",
+    "sa",
+    "sah",
+    "sat",
+    "sc",
+    "scn",
+    "sco",
+    "sd",
+    "se",
+    "sg",
+    "sh",
+    "shi",
+    "shn",
+    "si",
+    "simple",
+    "sk",
+    "skr",
+    "sl",
+    "sm",
+    "smn",
+    "sn",
+    "so",
+    "sq",
+    "sr",
+    "srn",
+    "ss",
+    "st",
+    "stq",
+    "su",
+    "sv",
+    "sw",
+    "szl",
+    "szy",
+    "ta",
+    "tay",
+    "tcy",
+    "te",
+    "tet",
+    "tg",
+    "th",
+    "ti",
+    "tk",
+    "tl",
+    "tly",
+    "tn",
+    "to",
+    "tpi",
+    "tr",
+    "trv",
+    "ts",
+    "tt",
+    "tum",
+    "tw",
+    "ty",
+    "tyv",
+    "udm",
+    "ug",
+    "uk",
+    "ur",
+    "uz",
+    "ve",
+    "vec",
+    "vep",
+    "vi",
+    "vls",
+    "vo",
+    "wa",
+    "war",
+    "wo",
+    "wuu",
+    "xal",
+    "xh",
+    "xmf",
+    "yi",
+    "yo",
+    "za",
+    "zea",
+    "zgh",
+    "zh",
+    "zh-classical",
+    "zh-min-nan",
+    "zh-yue",
+    "zu",
+];
+
+/// Script and region variants of languages, used in `<html lang>` and links, sorted.
+///
+/// These aren't separate editions, e.g. `zh-hans` articles are on `zh.wikipedia.org`.
+const LANG_VARIANTS: &[&str] = &[
+    "crh-cyrl", "crh-latn", "gan-hans", "gan-hant", "iu-cans", "iu-latn", "kk-arab", "kk-cn",
+    "kk-cyrl", "kk-kz", "kk-latn", "kk-tr", "ku-arab", "ku-latn", "shi-latn", "shi-tfng", "sr-ec",
+    "sr-el", "tg-cyrl", "tg-latn", "uz-cyrl", "uz-latn", "zh-cn", "zh-hans", "zh-hant", "zh-hk",
+    "zh-mo", "zh-my", "zh-sg", "zh-tw",
+];
+
+/// Old or alternate codes for editions.
+const LANG_ALIASES: &[(&str, &str)] = &[("be-x-old", "be-tarask"), ("nb", "no")];
+
+/// Get the code of the Wikipedia edition that articles in `lang` are in.
+///
+/// Codes are case-insensitive. For script and region variants (e.g. `zh-Hans-CN`) the longest prefix that is an edition is used.
+///
+/// ```
+/// use om_wikiparser::wm::wikipedia_edition;
+///
+/// assert_eq!(Some("zh-yue"), wikipedia_edition("zh-yue"));
+/// assert_eq!(Some("zh"), wikipedia_edition("zh-Hans"));
+/// assert_eq!(Some("sr"), wikipedia_edition("sr-el"));
+/// assert_eq!(Some("be-tarask"), wikipedia_edition("be-x-old"));
+/// assert_eq!(None, wikipedia_edition("eng"));
+/// ```
+pub fn wikipedia_edition(lang: &str) -> Option<&'static str> {
+    let lang = lang.to_ascii_lowercase();
+    if let Some((_, edition)) = LANG_ALIASES.iter().find(|(alias, _)| *alias == lang) {
+        return Some(edition);
+    }
+
+    let mut prefix = lang.as_str();
+    loop {
+        if let Ok(i) = WIKIPEDIA_LANGS.binary_search(&prefix) {
+            return Some(WIKIPEDIA_LANGS[i]);
+        }
+        prefix = prefix.rsplit_once('-')?.0;
+    }
+}
+
+/// Check if `lang` is the code of a Wikipedia edition, or a variant of one.
+///
+/// ```
+/// use om_wikiparser::wm::is_wikipedia_lang;
+///
+/// assert!(is_wikipedia_lang("en"));
+/// assert!(is_wikipedia_lang("simple"));
+/// assert!(is_wikipedia_lang("zh-hans"));
+/// assert!(is_wikipedia_lang("be-tarask"));
+///
+/// assert!(!is_wikipedia_lang("eng"));
+/// assert!(!is_wikipedia_lang("wiki"));
+/// ```
+pub fn is_wikipedia_lang(lang: &str) -> bool {
+    let lang = lang.to_ascii_lowercase();
+    WIKIPEDIA_LANGS.binary_search(&lang.as_str()).is_ok()
+        || LANG_VARIANTS.binary_search(&lang.as_str()).is_ok()
+        || LANG_ALIASES.iter().any(|(alias, _)| *alias == lang)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lists_are_sorted() {
+        for list in [WIKIPEDIA_LANGS, LANG_VARIANTS] {
+            assert!(list.windows(2).all(|w| w[0] < w[1]), "{list:?}");
+        }
+    }
+
+    #[test]
+    fn is_wikipedia_lang() {
+        for lang in [
+            "de",
+            "EN",
+            "simple",
+            "zh-yue",
+            "zh-min-nan",
+            "be-tarask",
+            "be-x-old",
+        ] {
+            assert!(super::is_wikipedia_lang(lang), "{lang:?} should be valid");
+        }
+        for variant in ["zh-hans", "zh-TW", "sr-el", "kk-latn"] {
+            assert!(
+                super::is_wikipedia_lang(variant),
+                "{variant:?} should be valid"
+            );
+        }
+        for invalid in ["", "eng", "wiki", "english", "zh-", "en-us"] {
+            assert!(
+                !super::is_wikipedia_lang(invalid),
+                "{invalid:?} should be invalid"
+            );
+        }
+    }
+}
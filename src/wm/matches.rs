@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use super::{WikidataQid, WikipediaTitleNorm};
+
+/// The requested QIDs and titles, and which of them were found while reading a dump.
+///
+/// ```
+/// # use std::str::FromStr;
+/// use om_wikiparser::wm::{MatchTracker, WikidataQid};
+///
+/// let q1 = WikidataQid::from_str("Q1").unwrap();
+/// let q2 = WikidataQid::from_str("Q2").unwrap();
+/// let mut tracker = MatchTracker::new([q1, q2].into(), Default::default());
+///
+/// assert!(tracker.match_qid(&WikidataQid::from_str("Q1").unwrap()));
+/// assert!(!tracker.match_qid(&WikidataQid::from_str("Q3").unwrap()));
+///
+/// let unmatched: Vec<_> = tracker.unmatched_qids().map(ToString::to_string).collect();
+/// assert_eq!(vec!["Q2"], unmatched);
+/// ```
+#[derive(Debug, Default)]
+pub struct MatchTracker {
+    wikidata_ids: HashSet<WikidataQid>,
+    wikipedia_titles: HashSet<WikipediaTitleNorm>,
+    matched_qids: HashSet<WikidataQid>,
+    matched_titles: HashSet<WikipediaTitleNorm>,
+}
+
+impl MatchTracker {
+    pub fn new(
+        wikidata_ids: HashSet<WikidataQid>,
+        wikipedia_titles: HashSet<WikipediaTitleNorm>,
+    ) -> Self {
+        Self {
+            wikidata_ids,
+            wikipedia_titles,
+            ..Default::default()
+        }
+    }
+
+    /// The requested QIDs.
+    pub fn wikidata_ids(&self) -> &HashSet<WikidataQid> {
+        &self.wikidata_ids
+    }
+
+    /// The requested titles.
+    pub fn wikipedia_titles(&self) -> &HashSet<WikipediaTitleNorm> {
+        &self.wikipedia_titles
+    }
+
+    /// If `qid` was requested, and record it as matched.
+    pub fn match_qid(&mut self, qid: &WikidataQid) -> bool {
+        let is_requested = self.wikidata_ids.contains(qid);
+        if is_requested {
+            self.matched_qids.insert(qid.clone());
+        }
+        is_requested
+    }
+
+    /// Keep the `titles` that were requested, and record them as matched.
+    pub fn match_titles(
+        &mut self,
+        titles: impl IntoIterator<Item = WikipediaTitleNorm>,
+    ) -> Vec<WikipediaTitleNorm> {
+        let matching: Vec<_> = titles
+            .into_iter()
+            .filter(|t| self.wikipedia_titles.contains(t))
+            .collect();
+        self.matched_titles.extend(matching.iter().cloned());
+        matching
+    }
+
+    /// The requested QIDs that were matched.
+    pub fn matched_qids(&self) -> &HashSet<WikidataQid> {
+        &self.matched_qids
+    }
+
+    /// The requested titles that were matched.
+    pub fn matched_titles(&self) -> &HashSet<WikipediaTitleNorm> {
+        &self.matched_titles
+    }
+
+    /// The requested QIDs that haven't been matched, in no particular order.
+    pub fn unmatched_qids(&self) -> impl Iterator<Item = &WikidataQid> {
+        self.wikidata_ids.difference(&self.matched_qids)
+    }
+
+    /// The requested titles that haven't been matched, in no particular order.
+    pub fn unmatched_titles(&self) -> impl Iterator<Item = &WikipediaTitleNorm> {
+        self.wikipedia_titles.difference(&self.matched_titles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unmatched() {
+        let qid = |id: &str| id.parse::<WikidataQid>().unwrap();
+        let title = |name: &str| WikipediaTitleNorm::from_title(name, "en").unwrap();
+
+        let mut tracker = MatchTracker::new(
+            HashSet::from([qid("Q1"), qid("Q2"), qid("Q3")]),
+            HashSet::from([title("Berlin"), title("Paris")]),
+        );
+        assert_eq!(3, tracker.unmatched_qids().count());
+        assert_eq!(2, tracker.unmatched_titles().count());
+
+        assert!(tracker.match_qid(&qid("Q1")));
+        assert!(tracker.match_qid(&qid("Q1")));
+        assert!(tracker.match_qid(&qid("Q3")));
+        assert!(!tracker.match_qid(&qid("Q4")));
+        assert_eq!(
+            vec![title("Paris")],
+            tracker.match_titles([title("Paris"), title("London")])
+        );
+
+        assert_eq!(
+            &HashSet::from([qid("Q1"), qid("Q3")]),
+            tracker.matched_qids()
+        );
+        assert_eq!(
+            vec![&qid("Q2")],
+            tracker.unmatched_qids().collect::<Vec<_>>()
+        );
+        assert_eq!(&HashSet::from([title("Paris")]), tracker.matched_titles());
+        assert_eq!(
+            vec![&title("Berlin")],
+            tracker.unmatched_titles().collect::<Vec<_>>()
+        );
+        assert_eq!(3, tracker.wikidata_ids().len());
+        assert_eq!(2, tracker.wikipedia_titles().len());
+    }
+}
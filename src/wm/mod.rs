@@ -1,60 +1,123 @@
 //! Wikimedia types
 use std::{
-    collections::HashSet, ffi::OsStr, fmt::Display, fs, num::ParseIntError, path::PathBuf,
+    cmp::Ordering,
+    collections::HashSet,
+    ffi::OsStr,
+    fmt::Display,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader},
+    num::ParseIntError,
+    path::PathBuf,
     str::FromStr,
 };
 
 use anyhow::{anyhow, bail, Context};
+use flate2::read::MultiGzDecoder;
 
 use url::Url;
 
+mod lang;
+pub use lang::{is_wikipedia_lang, wikipedia_edition};
+mod namespace;
+use namespace::non_article_namespace;
+mod matches;
+pub use matches::MatchTracker;
 mod page;
-pub use page::Page;
+pub use page::{Page, PageHeader};
 
-/// Read from a file of urls on each line.
-pub fn parse_wikidata_file(path: impl AsRef<OsStr>) -> anyhow::Result<HashSet<WikidataQid>> {
-    let contents = fs::read_to_string(path.as_ref())?;
-    Ok(contents
+/// Iterate over the lines of an input file and their line numbers, skipping blank lines and `#` comments.
+///
+/// Lines are read one at a time, so large files aren't loaded into memory.
+fn content_lines(reader: impl BufRead) -> impl Iterator<Item = io::Result<(usize, String)>> {
+    reader
         .lines()
         .enumerate()
-        .map(|(i, line)| {
-            WikidataQid::from_str(line).with_context(|| {
-                let line_num = i + 1;
-                format!("on line {line_num}: {line:?}")
-            })
-        })
-        .filter_map(|r| match r {
-            Ok(qid) => Some(qid),
-            Err(e) => {
-                warn!("Could not parse QID: {:#}", e);
-                None
+        .filter_map(|(i, line)| match line {
+            Ok(line) => {
+                let trimmed = line.trim();
+                let is_content = !trimmed.is_empty() && !trimmed.starts_with('#');
+                is_content.then(|| Ok((i + 1, line)))
             }
+            Err(e) => Some(Err(e)),
         })
-        .collect())
+}
+
+/// Open an input file, decompressing it if it starts with the gzip magic number.
+fn open_input(path: &OsStr) -> io::Result<Box<dyn BufRead>> {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        debug!("Decompressing gzipped file {:?}", path);
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Read from a file of urls on each line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+/// The file can be gzipped.
+pub fn parse_wikidata_file(path: impl AsRef<OsStr>) -> anyhow::Result<HashSet<WikidataQid>> {
+    let reader = open_input(path.as_ref())?;
+    let mut ids = HashSet::new();
+    for line in content_lines(reader) {
+        let (line_num, line) = line?;
+        match WikidataQid::from_str(&line).with_context(|| format!("on line {line_num}: {line:?}"))
+        {
+            Ok(qid) => {
+                ids.insert(qid);
+            }
+            Err(e) => warn!("Could not parse QID: {:#}", e),
+        }
+    }
+    Ok(ids)
 }
 
 /// Read article titles from a file of urls on each line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+/// The file can be gzipped.
+/// Urls with an unknown language code (see [is_wikipedia_lang]) are warned about,
+/// or skipped if `strict_lang` is set.
 pub fn parse_wikipedia_file(
     path: impl AsRef<OsStr>,
+    strict_lang: bool,
 ) -> anyhow::Result<HashSet<WikipediaTitleNorm>> {
-    let contents = fs::read_to_string(path.as_ref())?;
-    Ok(contents
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            WikipediaTitleNorm::from_url(line).with_context(|| {
-                let line_num = i + 1;
-                format!("on line {line_num}: {line:?}")
-            })
-        })
-        .filter_map(|r| match r {
-            Ok(qid) => Some(qid),
-            Err(e) => {
-                warn!("Could not parse wikipedia title: {:#}", e);
-                None
+    let reader = open_input(path.as_ref())?;
+    let mut titles = HashSet::new();
+    for line in content_lines(reader) {
+        let (line_num, line) = line?;
+        match parse_wikipedia_line(&line, line_num, strict_lang) {
+            Ok(title) => {
+                titles.insert(title);
             }
-        })
-        .collect())
+            Err(e) => warn!("Could not parse wikipedia title: {:#}", e),
+        }
+    }
+    Ok(titles)
+}
+
+fn parse_wikipedia_line(
+    line: &str,
+    line_num: usize,
+    strict_lang: bool,
+) -> anyhow::Result<WikipediaTitleNorm> {
+    let title = WikipediaTitleNorm::from_url(line)
+        .with_context(|| format!("on line {line_num}: {line:?}"))?;
+    if !is_wikipedia_lang(&title.lang) {
+        let message = format!(
+            "unknown wikipedia language {:?} on line {line_num}: {line:?}",
+            title.lang
+        );
+        if strict_lang {
+            bail!(message);
+        }
+        warn!("{message}");
+    }
+    Ok(title)
 }
 
 /// Wikidata QID/Q Number
@@ -75,7 +138,7 @@ pub fn parse_wikipedia_file(
 /// assert!(WikidataQid::from_str("Q").is_err());
 /// assert!(WikidataQid::from_str("").is_err());
 /// ```
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct WikidataQid(u32);
 
 impl FromStr for WikidataQid {
@@ -95,6 +158,66 @@ impl Display for WikidataQid {
 }
 
 impl WikidataQid {
+    /// The number of the QID, without the `Q` prefix.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use om_wikiparser::wm::WikidataQid;
+    /// assert_eq!(12345, WikidataQid::from_str("Q12345").unwrap().num());
+    /// ```
+    pub fn num(&self) -> u32 {
+        self.0
+    }
+
+    /// Parse the QID from a wikidata item or entity url, like `https://www.wikidata.org/wiki/Q12345`.
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use om_wikiparser::wm::WikidataQid;
+    /// let qid = WikidataQid::from_str("Q12345").unwrap();
+    /// assert_eq!(qid, WikidataQid::from_url("https://www.wikidata.org/wiki/Q12345").unwrap());
+    /// assert_eq!(qid, WikidataQid::from_url("http://wikidata.org/entity/Q12345").unwrap());
+    ///
+    /// assert!(WikidataQid::from_url("https://www.wikidata.org/wiki/Property:P31").is_err());
+    /// assert!(WikidataQid::from_url("https://en.wikipedia.org/wiki/Q12345").is_err());
+    /// ```
+    pub fn from_url(url: &str) -> anyhow::Result<Self> {
+        let url = Url::parse(url.trim())?;
+
+        let host = url.host_str().ok_or_else(|| anyhow!("Expected host"))?;
+        if host.strip_prefix("www.").unwrap_or(host) != "wikidata.org" {
+            bail!("Expected wikidata.org for domain")
+        }
+
+        let path = url.path();
+        let (root, qid) = path
+            .strip_prefix('/')
+            .unwrap_or(path)
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Expected at least two segments in path"))?;
+        if root != "wiki" && root != "entity" {
+            bail!("Expected 'wiki' or 'entity' as root path, got: {:?}", root)
+        }
+        if !qid.starts_with('Q') {
+            bail!("Expected a QID starting with 'Q', got: {:?}", qid)
+        }
+
+        Ok(Self::from_str(qid)?)
+    }
+
+    /// The canonical wikidata url of the item, the inverse of [WikidataQid::from_url].
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use om_wikiparser::wm::WikidataQid;
+    /// let qid = WikidataQid::from_str("Q12345").unwrap();
+    /// assert_eq!("https://www.wikidata.org/wiki/Q12345", qid.to_url());
+    /// assert_eq!(qid, WikidataQid::from_url(&qid.to_url()).unwrap());
+    /// ```
+    pub fn to_url(&self) -> String {
+        format!("https://www.wikidata.org/wiki/{self}")
+    }
+
     pub fn get_dir(&self, base: PathBuf) -> PathBuf {
         let mut path = base;
         path.push("wikidata");
@@ -119,19 +242,66 @@ impl WikidataQid {
 ///
 /// assert!(WikipediaTitleNorm::from_url("https://en.wikipedia.org/not_a_wiki_page").is_err());
 /// assert!(WikipediaTitleNorm::from_url("https://wikidata.org/wiki/Q12345").is_err());
+/// assert!(WikipediaTitleNorm::from_url("https://en.wikipedia.org/wiki/Talk:Article_Title").is_err());
+/// assert!(WikipediaTitleNorm::from_title("File:Bar.jpg", "en").is_err());
 ///
 /// assert!(
 ///     WikipediaTitleNorm::from_url("https://de.wikipedia.org/wiki/Breil/Brigels").unwrap() !=
 ///     WikipediaTitleNorm::from_url("https://de.wikipedia.org/wiki/Breil").unwrap()
 /// );
 /// ```
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+///
+/// Typographic apostrophes compare equal to ascii ones, see [WikipediaTitleNorm::match_chars].
+#[derive(Debug, Clone)]
 pub struct WikipediaTitleNorm {
     lang: String,
     name: String,
 }
 
+impl PartialEq for WikipediaTitleNorm {
+    fn eq(&self, other: &Self) -> bool {
+        self.lang == other.lang && self.match_chars().eq(other.match_chars())
+    }
+}
+
+impl Eq for WikipediaTitleNorm {}
+
+impl Hash for WikipediaTitleNorm {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lang.hash(state);
+        for c in self.match_chars() {
+            c.hash(state);
+        }
+    }
+}
+
+impl PartialOrd for WikipediaTitleNorm {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WikipediaTitleNorm {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.lang
+            .cmp(&other.lang)
+            .then_with(|| self.match_chars().cmp(other.match_chars()))
+    }
+}
+
 impl WikipediaTitleNorm {
+    /// Characters of the title used for comparisons, with `’` and `‘` replaced by `'`.
+    ///
+    /// MediaWiki treats these as different titles, but the typographic forms are usually redirects,
+    /// and OSM tags and urls use either.
+    /// The original name is kept for [WikipediaTitleNorm::get_dir], in case the typographic form is canonical.
+    fn match_chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.name.chars().map(|c| match c {
+            '\u{2018}' | '\u{2019}' => '\'',
+            c => c,
+        })
+    }
+
     fn normalize_title(title: &str) -> String {
         // TODO: Compare with map generator url creation, ensure covers all cases.
         title.trim().replace(' ', "_")
@@ -168,15 +338,44 @@ impl WikipediaTitleNorm {
     }
 
     // en:Article Title
+    //
+    // The tag is split on the first `:`, so the title can contain colons, e.g. `en:Star Wars: A New Hope`.
+    // If the title itself starts with a lowercase language code and a `:`, like `en: de:Artikel`,
+    // it is an interwiki link and the inner language is used, as Wikipedia resolves it.
+    // Article titles start with an uppercase letter, so they aren't mistaken for language codes.
     fn _from_osm_tag(tag: &str) -> anyhow::Result<Self> {
-        let (lang, title) = tag
+        let (mut lang, mut title) = tag
             .trim()
             .split_once(':')
             .ok_or_else(|| anyhow!("Expected ':'"))?;
 
+        while let Some((inner_lang, inner_title)) = title.trim_start().split_once(':') {
+            let inner_lang = inner_lang.trim_end();
+            let is_lowercase = inner_lang.chars().all(|c| !c.is_uppercase());
+            if inner_lang.is_empty() || !is_lowercase || !is_wikipedia_lang(inner_lang) {
+                break;
+            }
+            (lang, title) = (inner_lang, inner_title);
+        }
+
         Self::from_title(title, lang)
     }
 
+    // wikipedia=en:Article Title
+    // wikipedia:de=Artikel Titel
+    //
+    // Language-specific keys only have the title as their value, so the language comes from the key.
+    fn _from_osm_key_tag(key: &str, value: &str) -> anyhow::Result<Self> {
+        match key.trim().split_once(':') {
+            None if key.trim() == "wikipedia" => Self::_from_osm_tag(value),
+            Some(("wikipedia", lang)) => Self::from_title(value, lang),
+            _ => bail!(
+                "Expected 'wikipedia' or 'wikipedia:<lang>' key, got: {:?}",
+                key
+            ),
+        }
+    }
+
     pub fn from_title(title: &str, lang: &str) -> anyhow::Result<Self> {
         let title = title.trim();
         let lang = lang.trim();
@@ -186,11 +385,39 @@ impl WikipediaTitleNorm {
         if lang.is_empty() {
             bail!("lang cannot be empty or whitespace");
         }
+        let lang = match wikipedia_edition(lang) {
+            Some(edition) => edition.to_owned(),
+            None => lang.to_lowercase(),
+        };
+        if let Some(namespace) = non_article_namespace(title, &lang) {
+            bail!("title is in the non-article namespace {namespace:?}");
+        }
         let name = Self::normalize_title(title);
-        let lang = lang.to_owned();
         Ok(Self { name, lang })
     }
 
+    /// The language code of the wikipedia edition, e.g. `en` or `zh-yue`.
+    ///
+    /// ```
+    /// # use om_wikiparser::wm::WikipediaTitleNorm;
+    /// let title = WikipediaTitleNorm::from_url("https://de.wikipedia.org/wiki/Berlin_Hauptbahnhof").unwrap();
+    /// assert_eq!("de", title.lang());
+    /// ```
+    pub fn lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// The normalized title, with spaces replaced by underscores.
+    ///
+    /// ```
+    /// # use om_wikiparser::wm::WikipediaTitleNorm;
+    /// let title = WikipediaTitleNorm::from_title("Berlin Hauptbahnhof", "de").unwrap();
+    /// assert_eq!("Berlin_Hauptbahnhof", title.name());
+    /// ```
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn get_dir(&self, base: PathBuf) -> PathBuf {
         let mut path = base;
         // TODO: can use as_mut_os_string with 1.70.0
@@ -201,3 +428,179 @@ impl WikipediaTitleNorm {
         path
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn content_lines() {
+        let contents = "# Castles\n\nQ1\n  # indented comment\n   \nQ2 \r\nnot a qid";
+        assert_eq!(
+            vec![
+                (3, "Q1".to_owned()),
+                (6, "Q2 ".to_owned()),
+                (7, "not a qid".to_owned())
+            ],
+            super::content_lines(contents.as_bytes())
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_gzipped_files() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let gzip = |path: &PathBuf, contents: &str| {
+            let mut encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+            encoder.write_all(contents.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+        };
+
+        let contents = "Q1\nQ2\n# Q3\nQ4\n";
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, contents).unwrap();
+        let ids_gz = dir.path().join("ids.txt.gz");
+        gzip(&ids_gz, contents);
+        assert_eq!(
+            HashSet::from([WikidataQid(1), WikidataQid(2), WikidataQid(4)]),
+            parse_wikidata_file(&ids_gz).unwrap()
+        );
+        assert_eq!(
+            parse_wikidata_file(&ids).unwrap(),
+            parse_wikidata_file(&ids_gz).unwrap()
+        );
+
+        // Compression is detected from the contents, not the extension.
+        let urls = dir.path().join("urls");
+        gzip(&urls, "https://de.wikipedia.org/wiki/Berlin\n");
+        assert_eq!(
+            HashSet::from([WikipediaTitleNorm::from_title("Berlin", "de").unwrap()]),
+            parse_wikipedia_file(&urls, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_files_with_comments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let ids = dir.path().join("ids.txt");
+        fs::write(&ids, "# Castles\nQ1\n\n# Bridges\nQ2\n   \n").unwrap();
+        assert_eq!(
+            HashSet::from([WikidataQid(1), WikidataQid(2)]),
+            parse_wikidata_file(&ids).unwrap()
+        );
+
+        let urls = dir.path().join("urls.txt");
+        fs::write(
+            &urls,
+            "# Castles\n\nhttps://en.wikipedia.org/wiki/Neuschwanstein_Castle\n#https://en.wikipedia.org/wiki/Commented_Out\n",
+        )
+        .unwrap();
+        assert_eq!(
+            HashSet::from([WikipediaTitleNorm::from_title("Neuschwanstein Castle", "en").unwrap()]),
+            parse_wikipedia_file(&urls, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn apostrophes() {
+        let titles = [
+            WikipediaTitleNorm::from_url("https://en.wikipedia.org/wiki/McMullen's_Brewery")
+                .unwrap(),
+            WikipediaTitleNorm::from_url("https://en.wikipedia.org/wiki/McMullen%27s_Brewery")
+                .unwrap(),
+            WikipediaTitleNorm::_from_osm_tag("en:McMullen’s Brewery").unwrap(),
+        ];
+        for title in &titles[1..] {
+            assert_eq!(titles[0], *title);
+            assert_eq!(Ordering::Equal, titles[0].cmp(title));
+        }
+        assert_eq!(1, HashSet::<_>::from_iter(titles.iter()).len());
+
+        // The original form is kept for the directory.
+        assert_eq!(
+            PathBuf::from("en.wikipedia.org/wiki/McMullen’s_Brewery"),
+            titles[2].get_dir(PathBuf::new())
+        );
+
+        assert_ne!(
+            WikipediaTitleNorm::from_title("McMullens Brewery", "en").unwrap(),
+            titles[0]
+        );
+    }
+
+    #[test]
+    fn osm_tag_colons() {
+        let title = |title, lang| WikipediaTitleNorm::from_title(title, lang).unwrap();
+        let tag = |tag| WikipediaTitleNorm::_from_osm_tag(tag).unwrap();
+
+        assert_eq!(
+            title("Star Wars: A New Hope", "en"),
+            tag("en:Star Wars: A New Hope")
+        );
+        assert_eq!(
+            title("C++: The Language", "en"),
+            tag("en:C++: The Language")
+        );
+
+        // Interwiki prefixes use the innermost language.
+        assert_eq!(title("Berlin", "de"), tag("en: de:Berlin"));
+        assert_eq!(title("Berlin", "de"), tag("en:de:Berlin"));
+        assert_eq!(title("香港", "zh-yue"), tag("en:fr:zh-yue:香港"));
+
+        // Capitalized prefixes are part of the title, not languages.
+        assert_eq!(title("De: Berlin", "en"), tag("en:De: Berlin"));
+
+        assert!(WikipediaTitleNorm::_from_osm_tag("en:de:").is_err());
+    }
+
+    #[test]
+    fn osm_key_tag() {
+        let title = |title, lang| WikipediaTitleNorm::from_title(title, lang).unwrap();
+        let tag = |key, value| WikipediaTitleNorm::_from_osm_key_tag(key, value).unwrap();
+
+        assert_eq!(title("Berlin", "de"), tag("wikipedia:de", "Berlin"));
+        assert_eq!(title("Berlin", "de"), tag("wikipedia", "de:Berlin"));
+        assert_eq!(title("香港", "zh-yue"), tag("wikipedia:zh-yue", "香港"));
+        assert_eq!(
+            title("Star Wars: A New Hope", "en"),
+            tag("wikipedia:en", "Star Wars: A New Hope")
+        );
+
+        assert!(WikipediaTitleNorm::_from_osm_key_tag("wikidata", "Q64").is_err());
+        assert!(WikipediaTitleNorm::_from_osm_key_tag("name:de", "Berlin").is_err());
+        assert!(WikipediaTitleNorm::_from_osm_key_tag("wikipedia:de", "").is_err());
+    }
+
+    #[test]
+    fn variant_titles() {
+        let url =
+            WikipediaTitleNorm::from_url("https://zh-yue.wikipedia.org/wiki/%E9%A6%99%E6%B8%AF")
+                .unwrap();
+        let tag = WikipediaTitleNorm::_from_osm_tag("zh-yue:香港").unwrap();
+        let title = WikipediaTitleNorm::from_title("香港", "ZH-YUE").unwrap();
+        assert_eq!(url, tag);
+        assert_eq!(url, title);
+        assert_ne!(url, WikipediaTitleNorm::_from_osm_tag("zh:香港").unwrap());
+        assert_eq!(
+            PathBuf::from("base/zh-yue.wikipedia.org/wiki/香港"),
+            url.get_dir(PathBuf::from("base"))
+        );
+
+        // Script variants are in the main edition.
+        assert_eq!(
+            WikipediaTitleNorm::_from_osm_tag("sr:Београд").unwrap(),
+            WikipediaTitleNorm::_from_osm_tag("sr-ec:Београд").unwrap()
+        );
+        assert_eq!(
+            WikipediaTitleNorm::from_url("https://be-tarask.wikipedia.org/wiki/Менск").unwrap(),
+            WikipediaTitleNorm::from_url("https://be-x-old.wikipedia.org/wiki/Менск").unwrap()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Detection of titles outside of the main (article) namespace.
+//!
+//! See <https://en.wikipedia.org/wiki/Wikipedia:Namespace>.
+
+/// Canonical namespace names that are valid in every language, lowercase.
+const CANONICAL_NAMESPACES: &[&str] = &[
+    "talk",
+    "user",
+    "user talk",
+    "wikipedia",
+    "wikipedia talk",
+    "project",
+    "project talk",
+    "file",
+    "file talk",
+    "image",
+    "image talk",
+    "media",
+    "mediawiki",
+    "mediawiki talk",
+    "template",
+    "template talk",
+    "help",
+    "help talk",
+    "category",
+    "category talk",
+    "portal",
+    "portal talk",
+    "draft",
+    "draft talk",
+    "timedtext",
+    "timedtext talk",
+    "module",
+    "module talk",
+    "special",
+];
+
+/// Localized names of the most common namespaces, lowercase.
+const LOCALIZED_NAMESPACES: &[(&str, &[&str])] = &[
+    (
+        "de",
+        &[
+            "diskussion",
+            "benutzer",
+            "benutzerin",
+            "datei",
+            "vorlage",
+            "hilfe",
+            "kategorie",
+            "spezial",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "discusión",
+            "usuario",
+            "usuaria",
+            "archivo",
+            "plantilla",
+            "ayuda",
+            "categoría",
+            "especial",
+            "anexo",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "discussion",
+            "utilisateur",
+            "utilisatrice",
+            "fichier",
+            "modèle",
+            "aide",
+            "catégorie",
+            "spécial",
+        ],
+    ),
+    (
+        "it",
+        &["discussione", "utente", "categoria", "aiuto", "speciale"],
+    ),
+    (
+        "pt",
+        &[
+            "discussão",
+            "usuário",
+            "utilizador",
+            "ficheiro",
+            "arquivo",
+            "predefinição",
+            "ajuda",
+            "categoria",
+            "especial",
+        ],
+    ),
+    (
+        "ru",
+        &[
+            "обсуждение",
+            "участник",
+            "участница",
+            "файл",
+            "шаблон",
+            "справка",
+            "категория",
+            "служебная",
+        ],
+    ),
+];
+
+/// Get the namespace of `title` in the `lang` edition if it isn't an article, e.g. `Talk` for `Talk:Berlin`.
+///
+/// Titles with a colon that isn't a namespace, like `Star Wars: Episode IV`, are articles.
+pub fn non_article_namespace<'a>(title: &'a str, lang: &str) -> Option<&'a str> {
+    let (prefix, _) = title.split_once(':')?;
+    let namespace = prefix.trim().replace('_', " ").to_lowercase();
+
+    let is_namespace = CANONICAL_NAMESPACES.contains(&namespace.as_str())
+        || LOCALIZED_NAMESPACES
+            .iter()
+            .filter(|(l, _)| *l == lang)
+            .any(|(_, namespaces)| namespaces.contains(&namespace.as_str()));
+
+    is_namespace.then_some(prefix)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn non_article_namespace() {
+        for article in ["Berlin", "Star Wars: Episode IV – A New Hope", "Help!"] {
+            assert_eq!(None, super::non_article_namespace(article, "en"));
+        }
+
+        assert_eq!(
+            Some("Talk"),
+            super::non_article_namespace("Talk:Berlin", "en")
+        );
+        assert_eq!(
+            Some("File"),
+            super::non_article_namespace("File:Bar.jpg", "en")
+        );
+        assert_eq!(
+            Some("user_talk"),
+            super::non_article_namespace("user_talk:Example", "en")
+        );
+        assert_eq!(
+            Some("Datei"),
+            super::non_article_namespace("Datei:Berlin.jpg", "de")
+        );
+        // Localized names only apply to their own language.
+        assert_eq!(None, super::non_article_namespace("Datei:Berlin.jpg", "en"));
+    }
+}
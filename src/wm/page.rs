@@ -1,6 +1,7 @@
 use std::{iter, str::FromStr};
 
 use serde::Deserialize;
+use serde_json::value::RawValue;
 
 use super::{WikidataQid, WikipediaTitleNorm};
 
@@ -8,9 +9,11 @@ use super::{WikidataQid, WikipediaTitleNorm};
 /// Deserialized Wikimedia Enterprise API Article
 ///
 /// For all available fields, see <https://enterprise.wikimedia.com/docs/data-dictionary/>.
+///
+/// The article body is only deserialized as `B`, see [PageHeader] to skip it.
 #[allow(dead_code)] // TODO: reevaluate fields
-#[derive(Deserialize)]
-pub struct Page {
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Page<B = ArticleBody> {
     // TODO: Check if CoW has a performance impact.
     pub name: String,
     pub date_modified: String,
@@ -18,13 +21,60 @@ pub struct Page {
     #[serde(default)]
     pub url: String,
     pub main_entity: Option<Wikidata>,
-    // TODO: See what impact parsing/unescaping/allocating this has.
-    pub article_body: ArticleBody,
+    /// One-line summary of the article, e.g. `Capital and largest city of Germany`.
+    ///
+    /// Not included in all dumps.
+    #[serde(default, alias = "short_description")]
+    pub description: Option<String>,
+    pub article_body: B,
     #[serde(default)]
     pub redirects: Vec<Redirect>,
 }
 
+/// A [Page] with the article html borrowed from the line as escaped json, instead of being unescaped and allocated.
+///
+/// Most pages in a dump aren't extracted, so deserializing only this to match them is much faster.
+pub type PageHeader<'a> = Page<RawArticleBody<'a>>;
+
+impl<'a> PageHeader<'a> {
+    /// Deserialize the header of a page from a line of the dump.
+    ///
+    /// Unlike [Page::from_json], `json` is never modified, so it can be deserialized again as a full [Page].
+    pub fn from_json_header(json: &'a [u8]) -> anyhow::Result<Self> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    /// Unescape the article html into a full [Page], without parsing the rest of the line again.
+    pub fn into_page(self) -> anyhow::Result<Page> {
+        let html = serde_json::from_str(self.article_body.html.get())?;
+        Ok(Page {
+            name: self.name,
+            date_modified: self.date_modified,
+            in_language: self.in_language,
+            url: self.url,
+            main_entity: self.main_entity,
+            description: self.description,
+            article_body: ArticleBody { html },
+            redirects: self.redirects,
+        })
+    }
+}
+
 impl Page {
+    /// Deserialize a page from a line of the dump.
+    ///
+    /// With the `simd-json` feature the line is parsed in place, and the contents of `json` are unspecified afterwards.
+    pub fn from_json(json: &mut [u8]) -> anyhow::Result<Self> {
+        #[cfg(feature = "simd-json")]
+        let page = simd_json::from_slice(json)?;
+        #[cfg(not(feature = "simd-json"))]
+        let page = serde_json::from_slice(json)?;
+
+        Ok(page)
+    }
+}
+
+impl<B> Page<B> {
     pub fn wikidata(&self) -> Option<WikidataQid> {
         // TODO: return error
         self.main_entity
@@ -49,26 +99,115 @@ impl Page {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct Wikidata {
     pub identifier: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct ArticleBody {
-    // TODO: Look into RawValue to lazily parse/allocate this:
-    // https://docs.rs/serde_json/latest/serde_json/value/struct.RawValue.html
     pub html: String,
 }
 
+/// The article html of a [PageHeader], still escaped.
+#[derive(Debug, Deserialize)]
+pub struct RawArticleBody<'a> {
+    #[serde(borrow)]
+    pub html: &'a RawValue,
+}
+
 #[allow(dead_code)] // TODO: Reevaluate fields.
-#[derive(Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct Redirect {
     pub url: String,
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct Language {
     pub identifier: String,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LINE: &str = r#"{"name":"Berlin","date_modified":"2023-06-01T00:00:00Z","in_language":{"identifier":"de"},"url":"https://de.wikipedia.org/wiki/Berlin","main_entity":{"identifier":"Q64"},"article_body":{"html":"<p>Berlin ist die \"Hauptstadt\"\u00a0Deutschlands.</p>\n"},"redirects":[{"url":"https://de.wikipedia.org/wiki/Berlin_(Deutschland)","name":"Berlin (Deutschland)"}]}
+"#;
+
+    #[test]
+    fn from_json() {
+        let page = Page::from_json(&mut LINE.as_bytes().to_vec()).unwrap();
+        let expected: Page = serde_json::from_str(LINE).unwrap();
+        assert_eq!(expected, page);
+        assert_eq!(
+            "<p>Berlin ist die \"Hauptstadt\"\u{a0}Deutschlands.</p>\n",
+            page.article_body.html
+        );
+
+        assert!(Page::from_json(&mut b"{\"name\": ".to_vec()).is_err());
+    }
+
+    /// The simd-json parser deserializes the same pages as serde_json.
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn simd_json() {
+        let lines = [
+            LINE.to_owned(),
+            LINE.replacen(
+                r#""name":"Berlin","#,
+                r#""name":"Berlin","description":"Hauptstadt\tDeutschlands","#,
+                1,
+            ),
+            // Without any of the optional fields.
+            r#"{"name":"Berlin","date_modified":"2023-06-01T00:00:00Z","in_language":{"identifier":"de"},"main_entity":null,"article_body":{"html":""}}"#.to_owned(),
+        ];
+        for line in lines {
+            let expected: Page = serde_json::from_str(&line).unwrap();
+            let page: Page = simd_json::from_slice(&mut line.clone().into_bytes()).unwrap();
+            assert_eq!(expected, page, "{line}");
+        }
+    }
+
+    #[test]
+    fn header() {
+        let header = PageHeader::from_json_header(LINE.as_bytes()).unwrap();
+        let page = Page::from_json(&mut LINE.as_bytes().to_vec()).unwrap();
+
+        assert_eq!(page.name, header.name);
+        assert_eq!(page.in_language, header.in_language);
+        assert_eq!(page.url, header.url);
+        assert_eq!(page.wikidata(), header.wikidata());
+        assert_eq!(page.redirects, header.redirects);
+        assert_eq!(
+            page.all_titles().map(Result::unwrap).collect::<Vec<_>>(),
+            header.all_titles().map(Result::unwrap).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "<p>Berlin ist die \"Hauptstadt\"\u{a0}Deutschlands.</p>\n",
+            page.article_body.html
+        );
+
+        assert_eq!(page, header.into_page().unwrap());
+
+        // The body is still required.
+        let line = LINE.replace(r#""article_body":"#, r#""body":"#);
+        assert!(PageHeader::from_json_header(line.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn description() {
+        let page = Page::from_json(&mut LINE.as_bytes().to_vec()).unwrap();
+        assert_eq!(None, page.description);
+
+        for field in ["description", "short_description"] {
+            let line = LINE.replacen(
+                r#""name":"Berlin","#,
+                &format!(r#""name":"Berlin","{field}":"Hauptstadt Deutschlands","#),
+                1,
+            );
+            let page = Page::from_json(&mut line.into_bytes()).unwrap();
+            assert_eq!(Some("Hauptstadt Deutschlands"), page.description.as_deref());
+        }
+    }
+}